@@ -1,7 +1,10 @@
+// The benchmarks predate this lint and are kept as written.
+#![allow(clippy::unit_arg)]
+
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 #[cfg(feature = "asynchronous")]
 use revent::asynchronous::Mailer;
-use revent::{Channel, Node, Slot, Suspend};
+use revent::{node_dyn, Channel, Node, Slot, Suspend};
 
 fn criterion_benchmark(c: &mut Criterion) {
     #[cfg(feature = "asynchronous")]
@@ -50,7 +53,7 @@ fn criterion_benchmark(c: &mut Criterion) {
 
         let mut channel: Channel<dyn Trait> = Channel::new();
 
-        channel.insert(0, Node::new(()));
+        channel.insert(0, node_dyn!(Node::new(()), dyn Trait));
 
         b.iter(|| {
             channel.emit(|x| x.function());
@@ -76,7 +79,7 @@ fn criterion_benchmark(c: &mut Criterion) {
 
         struct Subscriber {
             value: usize,
-        };
+        }
 
         impl Trait for Subscriber {
             fn function(&mut self, hub: &Hub) {
@@ -103,8 +106,8 @@ fn criterion_benchmark(c: &mut Criterion) {
         }
 
         let x = Node::new(Subscriber { value: 1000 });
-        hub.channel.insert(0, x.clone());
-        hub.reset.insert(x);
+        hub.channel.insert(0, node_dyn!(x.clone(), dyn Trait));
+        hub.reset.insert(node_dyn!(x, dyn Reset));
 
         b.iter(|| {
             hub.channel.emit(|x| {
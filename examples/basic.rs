@@ -1,4 +1,4 @@
-use revent::{Channel, Node, Slot, Suspend};
+use revent::{node_dyn, Channel, Node, Slot, Suspend};
 
 // Create signal traits.
 trait SignalA {
@@ -63,12 +63,12 @@ fn main() {
     let mut hub = MyHub::default();
 
     // Insert nodes into the hub. Nodes can be cloned and used on their own using the `emit`
-    // method.
+    // method. `node_dyn` converts them into nodes of the signal traits.
     let a = Node::new(A);
-    hub.signal_a.insert(0, a.clone());
-    hub.signal_b.insert(0, a.clone());
-    hub.signal_b.insert(0, Node::new(B));
-    hub.signal_c.insert(Node::new(C));
+    hub.signal_a.insert(0, node_dyn!(a.clone(), dyn SignalA));
+    hub.signal_b.insert(0, node_dyn!(a.clone(), dyn SignalB));
+    hub.signal_b.insert(0, node_dyn!(Node::new(B), dyn SignalB));
+    hub.signal_c.insert(node_dyn!(Node::new(C), dyn SignalC));
 
     // Run `a` and call `signal_a`.
     a.emit(|x| {
@@ -0,0 +1,79 @@
+use revent::{signal, Channel, Hub, Node, Slot, Subscriber, Suspend};
+
+// Create signal traits. `#[signal]` generates emit helpers for channels and slots of each trait.
+#[signal]
+trait SignalA {
+    fn signal_a(&mut self, hub: &MyHub);
+}
+#[signal]
+trait SignalB {
+    fn signal_b(&mut self, hub: &MyHub);
+}
+#[signal]
+trait SignalC {
+    fn signal_c(&mut self) -> usize;
+}
+
+// Create a struct of channels and slots based on your signal traits. Adding a signal is a
+// single field.
+#[derive(Hub)]
+struct MyHub {
+    signal_a: Channel<dyn SignalA>,
+    signal_b: Channel<dyn SignalB>,
+    signal_c: Slot<dyn SignalC>,
+}
+
+// List the fields each type listens to, `MyHub::subscribe` attaches a node to all of them.
+#[derive(Subscriber)]
+#[subscribe(MyHub: signal_a, signal_b)]
+struct A;
+#[derive(Subscriber)]
+#[subscribe(MyHub: signal_b)]
+struct B;
+#[derive(Subscriber)]
+#[subscribe(MyHub: signal_c)]
+struct C;
+
+impl SignalA for A {
+    fn signal_a(&mut self, hub: &MyHub) {
+        println!("A::signal_a: {:?}", self as *mut _);
+
+        self.suspend(|| {
+            // Instead of `hub.signal_b.emit(|x| x.signal_b(hub))`.
+            hub.signal_b.signal_b(hub);
+        });
+    }
+}
+impl SignalB for A {
+    fn signal_b(&mut self, _: &MyHub) {
+        println!("A::signal_b: {:?}", self as *mut _);
+    }
+}
+impl SignalB for B {
+    fn signal_b(&mut self, hub: &MyHub) {
+        println!("B::signal_b: {:?}", self as *mut _);
+        let value = hub.signal_c.signal_c();
+        println!("B::signal_b: got {}", value);
+    }
+}
+impl SignalC for C {
+    fn signal_c(&mut self) -> usize {
+        println!("C::signal_c: {:?}", self as *mut _);
+        123
+    }
+}
+
+fn main() {
+    let mut hub = MyHub::default();
+
+    let a = Node::new(A);
+    let _a = hub.subscribe(&a);
+    let _b = hub.subscribe(&Node::new(B));
+    let _c = hub.subscribe(&Node::new(C));
+
+    a.emit(|x| {
+        x.signal_a(&hub);
+    });
+
+    hub.signal_a.signal_a(&hub);
+}
@@ -0,0 +1,332 @@
+//! Derive macros for [revent](https://docs.rs/revent).
+//!
+//! These macros are re-exported by `revent` when its `derive` feature is enabled, use them
+//! through `revent::signal`, `revent::Hub` and `revent::Subscriber`.
+#![deny(
+    missing_docs,
+    trivial_casts,
+    trivial_numeric_casts,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{
+    parse::{Parse, ParseStream},
+    parse_macro_input,
+    punctuated::Punctuated,
+    Data, DeriveInput, Error, FnArg, Ident, ItemTrait, Result, ReturnType, Token, TraitItem, Type,
+};
+
+/// Generate emit helpers for a signal trait.
+///
+/// For a trait `Signal`, this generates the traits `SignalChannelExt`, implemented for
+/// `Channel<dyn Signal>`, and `SignalSlotExt`, implemented for `Slot<dyn Signal>`. They contain
+/// one method per method of `Signal` taking `&self` or `&mut self`, which emits that method on
+/// every node of the container. Methods taking `&self` are emitted with `emit_ref`, so they only
+/// borrow each node shared.
+///
+/// Channel helpers return `()` for methods returning `()`, and otherwise a `Vec` of the results
+/// of each node. Since a channel calls each node, all arguments must implement `Clone`.
+///
+/// Also implements `Upcast` for `dyn Signal`, converting nodes of any type implementing `Signal`.
+///
+/// Methods with generic or lifetime parameters are rejected with a compile error.
+#[proc_macro_attribute]
+pub fn signal(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(item as ItemTrait);
+
+    let expanded = if attr.is_empty() {
+        expand_signal(&input)
+    } else {
+        Err(Error::new(
+            proc_macro2::Span::call_site(),
+            "revent: signal: unexpected arguments",
+        ))
+    };
+
+    match expanded {
+        Ok(tokens) => quote!(#input #tokens).into(),
+        Err(error) => {
+            let error = error.to_compile_error();
+            quote!(#input #error).into()
+        }
+    }
+}
+
+fn expand_signal(input: &ItemTrait) -> Result<TokenStream2> {
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &input.generics,
+            "revent: signal: generic traits are not supported",
+        ));
+    }
+
+    let visibility = &input.vis;
+    let name = &input.ident;
+    let channel_ext = format_ident!("{}ChannelExt", name);
+    let slot_ext = format_ident!("{}SlotExt", name);
+
+    let mut channel_declarations = vec![];
+    let mut channel_definitions = vec![];
+    let mut slot_declarations = vec![];
+    let mut slot_definitions = vec![];
+
+    for item in &input.items {
+        let method = match item {
+            TraitItem::Method(method) => method,
+            _ => continue,
+        };
+        let signature = &method.sig;
+
+        // Only methods callable on a `&mut dyn Trait` can be emitted, those taking `&self` only
+        // borrow each node shared.
+        let shared = match signature.inputs.first() {
+            Some(FnArg::Receiver(receiver)) if receiver.reference.is_some() => {
+                receiver.mutability.is_none()
+            }
+            _ => continue,
+        };
+        if !signature.generics.params.is_empty() {
+            return Err(Error::new_spanned(
+                signature,
+                "revent: signal: methods with generic or lifetime parameters are not supported",
+            ));
+        }
+
+        let ident = &signature.ident;
+        let mut names = vec![];
+        let mut types = vec![];
+        for (index, argument) in signature.inputs.iter().skip(1).enumerate() {
+            if let FnArg::Typed(argument) = argument {
+                names.push(format_ident!("argument{}", index));
+                types.push(&argument.ty);
+            }
+        }
+
+        let emit = if shared {
+            quote!(emit_ref)
+        } else {
+            quote!(emit)
+        };
+        let (channel_output, channel_body) = match &signature.output {
+            ReturnType::Default => (
+                quote!(()),
+                quote! {
+                    self.#emit(|x| {
+                        x.#ident(#(::core::clone::Clone::clone(&#names)),*);
+                    })
+                },
+            ),
+            ReturnType::Type(_, output) if shared => (
+                quote!(::std::vec::Vec<#output>),
+                quote! {
+                    let mut results = ::std::vec::Vec::new();
+                    self.emit_ref(|x| {
+                        results.push(x.#ident(#(::core::clone::Clone::clone(&#names)),*));
+                    });
+                    results
+                },
+            ),
+            ReturnType::Type(_, output) => (
+                quote!(::std::vec::Vec<#output>),
+                quote! {
+                    self.emit_map(|x| x.#ident(#(::core::clone::Clone::clone(&#names)),*))
+                },
+            ),
+        };
+        let slot_output = match &signature.output {
+            ReturnType::Default => quote!(()),
+            ReturnType::Type(_, output) => quote!(#output),
+        };
+
+        let documentation = format!("Emit `{}::{}` on every node.", name, ident);
+        channel_declarations.push(quote! {
+            #[doc = #documentation]
+            fn #ident(&self, #(#names: #types),*) -> #channel_output;
+        });
+        channel_definitions.push(quote! {
+            fn #ident(&self, #(#names: #types),*) -> #channel_output {
+                #channel_body
+            }
+        });
+
+        let documentation = format!("Emit `{}::{}` on the node in the slot.", name, ident);
+        slot_declarations.push(quote! {
+            #[doc = #documentation]
+            fn #ident(&self, #(#names: #types),*) -> #slot_output;
+        });
+        slot_definitions.push(quote! {
+            fn #ident(&self, #(#names: #types),*) -> #slot_output {
+                self.#emit(|x| x.#ident(#(#names),*))
+            }
+        });
+    }
+
+    let channel_documentation = format!("Emit helpers for `Channel<dyn {}>`.", name);
+    let slot_documentation = format!("Emit helpers for `Slot<dyn {}>`.", name);
+
+    Ok(quote! {
+        #[doc = #channel_documentation]
+        #visibility trait #channel_ext {
+            #(#channel_declarations)*
+        }
+
+        impl #channel_ext for ::revent::Channel<dyn #name> {
+            #(#channel_definitions)*
+        }
+
+        #[doc = #slot_documentation]
+        #visibility trait #slot_ext {
+            #(#slot_declarations)*
+        }
+
+        impl #slot_ext for ::revent::Slot<dyn #name> {
+            #(#slot_definitions)*
+        }
+
+        impl<Item: #name + 'static> ::revent::Upcast<Item> for dyn #name {
+            fn upcast(node: ::revent::Node<Item>) -> ::revent::Node<Self> {
+                ::revent::node_dyn!(node, Self)
+            }
+        }
+    })
+}
+
+/// Derive a signal hub from a struct of `Channel`s and `Slot`s.
+///
+/// Implements `Default` by defaulting every field, creating empty channels and slots. Also adds
+/// an inherent `subscribe` method which subscribes a node to every field its type listens to,
+/// see `#[derive(Subscriber)]`.
+#[proc_macro_derive(Hub)]
+pub fn hub(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match expand_hub(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+fn expand_hub(input: &DeriveInput) -> Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => {
+            return Err(Error::new_spanned(
+                input,
+                "revent: hub: only structs can be derived",
+            ))
+        }
+    };
+
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+
+    let defaults = fields.iter().enumerate().map(|(index, field)| {
+        let value = quote!(::core::default::Default::default());
+        match &field.ident {
+            Some(ident) => quote!(#ident: #value),
+            None => {
+                let index = syn::Index::from(index);
+                quote!(#index: #value)
+            }
+        }
+    });
+
+    Ok(quote! {
+        impl #impl_generics ::core::default::Default for #name #type_generics #where_clause {
+            fn default() -> Self {
+                Self {
+                    #(#defaults),*
+                }
+            }
+        }
+
+        impl #impl_generics #name #type_generics #where_clause {
+            /// Subscribe a node to every channel and slot of this hub its type listens to.
+            pub fn subscribe<S: ::revent::Subscriber<Self>>(
+                &mut self,
+                node: &::revent::Node<S>,
+            ) -> ::revent::Subscriptions {
+                <S as ::revent::Subscriber<Self>>::subscribe(node, self)
+            }
+        }
+    })
+}
+
+/// Derive `Subscriber` for the hubs listed in `#[subscribe]` attributes.
+///
+/// Each attribute names a hub followed by the fields the type listens to, for instance
+/// `#[subscribe(MyHub: signal_a, signal_b)]`. Subscribing a node attaches it to each listed
+/// field in order. The fields must hold trait objects of `#[signal]` traits, or otherwise
+/// implement `Upcast` for the node type.
+#[proc_macro_derive(Subscriber, attributes(subscribe))]
+pub fn subscriber(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match expand_subscriber(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+struct Subscribe {
+    hub: Type,
+    fields: Punctuated<Ident, Token![,]>,
+}
+
+impl Parse for Subscribe {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let hub = input.parse()?;
+        input.parse::<Token![:]>()?;
+        let fields = Punctuated::parse_terminated(input)?;
+        Ok(Self { hub, fields })
+    }
+}
+
+fn expand_subscriber(input: &DeriveInput) -> Result<TokenStream2> {
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+
+    let mut implementations = vec![];
+    for attribute in &input.attrs {
+        if !attribute.path.is_ident("subscribe") {
+            continue;
+        }
+        let Subscribe { hub, fields } = attribute.parse_args()?;
+        let fields = fields.iter();
+
+        implementations.push(quote! {
+            impl #impl_generics ::revent::Subscriber<#hub> for #name #type_generics #where_clause {
+                fn subscribe(
+                    node: &::revent::Node<Self>,
+                    hub: &mut #hub,
+                ) -> ::revent::Subscriptions {
+                    let mut subscriptions = ::revent::Subscriptions::new();
+                    #(
+                        subscriptions.push(::revent::Attach::attach(
+                            &mut hub.#fields,
+                            ::revent::Upcast::upcast(
+                                <::revent::Node<Self> as ::core::clone::Clone>::clone(node),
+                            ),
+                        ));
+                    )*
+                    subscriptions
+                }
+            }
+        });
+    }
+
+    if implementations.is_empty() {
+        return Err(Error::new_spanned(
+            input,
+            "revent: subscriber: expected at least one #[subscribe(Hub: fields...)] attribute",
+        ));
+    }
+
+    Ok(quote!(#(#implementations)*))
+}
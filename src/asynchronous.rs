@@ -43,7 +43,7 @@ impl<T: Clone + Send> Mailer<T> {
     /// the receivers are at capacity.
     pub fn send(&self, item: T) {
         let mut senders = self.senders.lock().unwrap();
-        senders.0.drain_filter(|x| x.send(item.clone()).is_err());
+        senders.0.retain(|x| x.send(item.clone()).is_ok());
         senders.1 = Some(item);
     }
 
@@ -1,12 +1,28 @@
-use crate::{Node, Trace};
-use isize_vec::IsizeVec;
+use crate::{
+    node::Link,
+    order::{self, Constraint, Listener},
+    stack_dump,
+    subscription::{Attach, Expired, Subscription, SubscriptionId},
+    trace::Indent,
+    Error, Node, Trace, TraceKind, WeakNode,
+};
+use std::{
+    any,
+    cell::{Cell, RefCell, UnsafeCell},
+    collections::{btree_map, BTreeMap},
+    convert::Infallible,
+    fmt, mem,
+    ops::ControlFlow,
+    rc::Weak,
+    slice,
+};
 
 /// Container for multiple [Node]s.
 ///
 /// ```
 /// use revent::{Channel, Node};
 ///
-/// let mut channel = Channel::new();
+/// let channel = Channel::new();
 ///
 /// for number in 0..10 {
 ///     channel.insert(0, Node::new(number));
@@ -16,11 +32,113 @@ use isize_vec::IsizeVec;
 ///     println!("{}", x);
 /// });
 /// ```
+///
+/// # Mutation during emission #
+///
+/// Nodes can be inserted and removed through a shared reference, also from within a handler
+/// that is currently being emitted by this channel. Such changes are deferred: every emission
+/// (including nested ones) sees the channel as it was when the outermost emission started, and
+/// the changes are applied in the order they were requested once the outermost emission
+/// finishes.
+///
+/// ```
+/// use revent::{Channel, Node};
+///
+/// let channel = Channel::new();
+/// channel.insert(0, Node::new(0));
+///
+/// let mut count = 0;
+/// channel.emit(|_| {
+///     channel.insert(0, Node::new(1));
+///     count += 1;
+/// });
+/// assert_eq!(count, 1);
+///
+/// channel.emit(|_| count += 1);
+/// assert_eq!(count, 3);
+/// ```
+///
+/// The exception is a node whose [Subscription] or strong [Node]s are dropped, which is skipped
+/// immediately.
 pub struct Channel<T: ?Sized> {
-    items: IsizeVec<Node<T>>,
+    // Only mutated by `Channel::flush`, once no emission is borrowing it.
+    items: UnsafeCell<BTreeMap<SubscriptionId, Entry<T>>>,
+    // Emission order of `items` if it differs from the order of their identifiers. Only
+    // mutated by `Channel::entries` once `flush` marked it stale.
+    order: UnsafeCell<Option<Vec<SubscriptionId>>>,
+    stale: Cell<bool>,
+    // Set once a node is inserted with an ordering constraint.
+    constrained: Cell<bool>,
+    pending: RefCell<Vec<Change<T>>>,
+    emitting: Cell<usize>,
+    sequence: Cell<isize>,
+    expired: Expired,
+    name: Option<&'static str>,
     trace: Trace,
 }
 
+struct Entry<T: ?Sized> {
+    node: Link<T>,
+    token: Option<Weak<()>>,
+    constraint: Option<Constraint>,
+    expired: Cell<bool>,
+}
+
+impl<T: ?Sized> Entry<T> {
+    fn new(node: Link<T>, token: Option<Weak<()>>) -> Self {
+        Self {
+            node,
+            token,
+            constraint: None,
+            expired: Cell::new(false),
+        }
+    }
+
+    fn listener(&self, id: SubscriptionId) -> Listener {
+        let (name, label) = self.node.names().unwrap_or((None, any::type_name::<T>()));
+        Listener {
+            id,
+            name,
+            label,
+            constraint: self.constraint,
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        self.token.as_ref().map_or(true, |x| x.strong_count() > 0)
+    }
+}
+
+enum Change<T: ?Sized> {
+    Insert(SubscriptionId, Entry<T>),
+    Remove(Node<T>),
+    Clear,
+}
+
+/// Marks a channel as emitting, applies deferred changes when the outermost guard is dropped.
+struct Emitting<'a, T: ?Sized> {
+    channel: &'a Channel<T>,
+}
+
+impl<'a, T: ?Sized> Emitting<'a, T> {
+    fn new(channel: &'a Channel<T>) -> Self {
+        channel.emitting.set(channel.emitting.get() + 1);
+        Self { channel }
+    }
+}
+
+impl<T: ?Sized> Drop for Emitting<'_, T> {
+    fn drop(&mut self) {
+        let emitting = &self.channel.emitting;
+        if emitting.get() == 1 {
+            // Still counted as emitting, so changes requested while flushing are deferred to the
+            // next iteration of the flush loop.
+            self.channel.flush();
+        }
+        emitting.set(emitting.get() - 1);
+    }
+}
+
 impl<T: ?Sized> Default for Channel<T> {
     fn default() -> Self {
         Self::new()
@@ -30,27 +148,201 @@ impl<T: ?Sized> Default for Channel<T> {
 impl<T: ?Sized> Channel<T> {
     /// Create a new channel.
     pub fn new() -> Self {
-        Self {
-            items: IsizeVec::default(),
-            trace: Trace::empty(),
-        }
+        Self::new_with_trace_object(None, Trace::empty())
+    }
+
+    /// Create a new channel with a name.
+    ///
+    /// The name is used instead of the type name in panic messages, traces and
+    /// [Debug](std::fmt::Debug) output.
+    pub fn named(name: &'static str) -> Self {
+        Self::new_with_trace_object(Some(name), Trace::empty())
     }
 
     /// Create a new channel with a trace object.
     pub fn new_with_trace(trace: impl Fn(usize) + 'static) -> Self {
+        Self::new_with_trace_object(None, Trace::new(trace))
+    }
+
+    fn new_with_trace_object(name: Option<&'static str>, trace: Trace) -> Self {
         Self {
-            items: IsizeVec::default(),
-            trace: Trace::new(trace),
+            items: UnsafeCell::new(BTreeMap::new()),
+            order: UnsafeCell::new(None),
+            stale: Cell::new(false),
+            constrained: Cell::new(false),
+            pending: RefCell::new(Vec::new()),
+            emitting: Cell::new(0),
+            sequence: Cell::new(0),
+            expired: Expired::default(),
+            name,
+            trace,
         }
     }
 
+    /// Name given to this channel by [Channel::named], if any.
+    pub fn name(&self) -> Option<&'static str> {
+        self.name
+    }
+
     /// Insert a node into this channel.
     ///
     /// The value `relative` indicates where the node will be put in the list relative to other
     /// nodes. If two nodes have the same `relative` value, then the node will be prepended if it
     /// is signed, and appended if unsigned.
-    pub fn insert(&mut self, relative: isize, item: Node<T>) {
-        self.items.insert(relative, item);
+    ///
+    /// Returns an identifier which can be used to [unsubscribe](Channel::unsubscribe) this
+    /// particular insertion.
+    pub fn insert(&self, relative: isize, item: Node<T>) -> SubscriptionId {
+        let id = self.next_id(relative);
+        self.change(Change::Insert(id, Entry::new(Link::Strong(item), None)));
+        id
+    }
+
+    /// Insert a node into this channel, to be emitted after all nodes [named](Node::named)
+    /// `name`.
+    ///
+    /// Constraints allow listeners to be ordered without agreeing on `relative` values.
+    /// Otherwise the node is ordered as if inserted with a `relative` value of `0`. Constraints
+    /// naming no node in this channel have no effect until such a node is inserted.
+    ///
+    /// ```
+    /// use revent::{Channel, Node};
+    ///
+    /// let channel = Channel::new();
+    ///
+    /// channel.insert_after("physics", Node::named("render", "render"));
+    /// channel.insert(0, Node::named("physics", "physics"));
+    /// channel.insert_before("physics", Node::named("input", "input"));
+    ///
+    /// assert_eq!(channel.emit_map(|x| *x), ["input", "physics", "render"]);
+    /// ```
+    ///
+    /// # Panics #
+    ///
+    /// Panics if the constraint completes a cycle, see [Channel::try_insert_after].
+    pub fn insert_after(&self, name: &'static str, item: Node<T>) -> SubscriptionId {
+        self.insert_constrained(Constraint::After(name), item)
+    }
+
+    /// Insert a node into this channel, to be emitted before all nodes [named](Node::named)
+    /// `name`.
+    ///
+    /// See [insert_after](Channel::insert_after).
+    ///
+    /// # Panics #
+    ///
+    /// Panics if the constraint completes a cycle, see [Channel::try_insert_before].
+    pub fn insert_before(&self, name: &'static str, item: Node<T>) -> SubscriptionId {
+        self.insert_constrained(Constraint::Before(name), item)
+    }
+
+    /// Insert a node to be emitted after all nodes named `name`, returning an [Error] instead of
+    /// panicking.
+    ///
+    /// Fails with [Error::OrderingCycle] if the constraints of this channel would contradict
+    /// each other, in which case the node is not inserted.
+    ///
+    /// ```
+    /// use revent::{Channel, Error, Node};
+    ///
+    /// let channel = Channel::new();
+    ///
+    /// channel.insert_after("b", Node::named("a", ()));
+    /// assert_eq!(
+    ///     channel.try_insert_after("a", Node::named("b", ())),
+    ///     Err(Error::OrderingCycle(vec!["b", "a", "b"]))
+    /// );
+    /// ```
+    pub fn try_insert_after(
+        &self,
+        name: &'static str,
+        item: Node<T>,
+    ) -> Result<SubscriptionId, Error> {
+        self.try_insert_constrained(Constraint::After(name), item)
+    }
+
+    /// Insert a node to be emitted before all nodes named `name`, returning an [Error] instead
+    /// of panicking.
+    ///
+    /// See [try_insert_after](Channel::try_insert_after).
+    pub fn try_insert_before(
+        &self,
+        name: &'static str,
+        item: Node<T>,
+    ) -> Result<SubscriptionId, Error> {
+        self.try_insert_constrained(Constraint::Before(name), item)
+    }
+
+    fn insert_constrained(&self, constraint: Constraint, item: Node<T>) -> SubscriptionId {
+        match self.try_insert_constrained(constraint, item) {
+            Ok(id) => id,
+            Err(error) => panic!("revent: insert: {} (in channel {})", error, self.label()),
+        }
+    }
+
+    fn try_insert_constrained(
+        &self,
+        constraint: Constraint,
+        item: Node<T>,
+    ) -> Result<SubscriptionId, Error> {
+        self.constrained.set(true);
+        let mut entry = Entry::new(Link::Strong(item), None);
+        entry.constraint = Some(constraint);
+
+        let id = self.next_id(0);
+        self.check_order(id, &entry)?;
+        self.change(Change::Insert(id, entry));
+        Ok(id)
+    }
+
+    /// Insert a weak node into this channel.
+    ///
+    /// The node is skipped once all strong [Node]s of `item` are dropped, and removed from the
+    /// channel after the next emission.
+    ///
+    /// Ordering follows the same rules as [insert](Channel::insert).
+    pub fn insert_weak(&self, relative: isize, item: WeakNode<T>) -> SubscriptionId {
+        let id = self.next_id(relative);
+        self.change(Change::Insert(id, Entry::new(Link::Weak(item), None)));
+        id
+    }
+
+    /// Insert a node into this channel and return a guard that removes it when dropped.
+    ///
+    /// Ordering follows the same rules as [insert](Channel::insert).
+    ///
+    /// ```
+    /// use revent::{Channel, Node};
+    ///
+    /// let channel = Channel::new();
+    ///
+    /// let subscription = channel.subscribe(0, Node::new(123));
+    /// drop(subscription);
+    ///
+    /// channel.emit(|_| {
+    ///     unreachable!();
+    /// });
+    /// ```
+    pub fn subscribe(&self, relative: isize, item: Node<T>) -> Subscription {
+        let id = self.next_id(relative);
+        let subscription = Subscription::new(&self.expired, id);
+        let entry = Entry::new(Link::Strong(item), Some(subscription.token()));
+        self.change(Change::Insert(id, entry));
+        subscription
+    }
+
+    /// Remove a single insertion from this channel.
+    ///
+    /// Other insertions of the same node are left untouched. Returns the removed node, or `None`
+    /// if the insertion was already removed or was a [WeakNode] that has been dropped.
+    ///
+    /// # Performance #
+    ///
+    /// Logarithmic in the number of nodes in this channel.
+    pub fn unsubscribe(&self, id: SubscriptionId) -> Option<Node<T>> {
+        let node = self.detach(id);
+        self.apply();
+        node
     }
 
     /// Remove all occurrences of a node from this channel.
@@ -58,32 +350,699 @@ impl<T: ?Sized> Channel<T> {
     /// # Performance #
     ///
     /// Performs a linear scan and retains only those nodes that do not match.
-    pub fn remove(&mut self, item: &Node<T>) {
-        self.items.retain(|x| !Node::<T>::ptr_eq(item, x));
+    pub fn remove(&self, item: &Node<T>) {
+        self.change(Change::Remove(item.clone()));
+    }
+
+    /// Number of nodes in this channel.
+    ///
+    /// Like all introspection methods, this already reflects changes that are deferred by an
+    /// ongoing emission. A node inserted more than once is counted for each insertion, nodes
+    /// whose [Subscription] or strong [Node]s were dropped are not counted.
+    ///
+    /// ```
+    /// use revent::{Channel, Node};
+    ///
+    /// let channel = Channel::new();
+    /// assert!(channel.is_empty());
+    ///
+    /// let node = Node::new(0);
+    /// channel.insert(0, node.clone());
+    /// channel.insert(1, node.clone());
+    /// channel.insert(2, Node::new(1));
+    ///
+    /// channel.emit(|_| channel.remove(&node));
+    /// assert_eq!(channel.len(), 1);
+    /// ```
+    ///
+    /// # Performance #
+    ///
+    /// Linear in the number of nodes in this channel, as are [is_empty](Channel::is_empty) and
+    /// [contains](Channel::contains). Methods observing the emission order, like
+    /// [iter](Channel::iter), also copy and sort the nodes.
+    pub fn len(&self) -> usize {
+        self.count(|_| true)
+    }
+
+    /// Returns true if this channel contains no nodes.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns true if `item` is inserted into this channel.
+    pub fn contains(&self, item: &Node<T>) -> bool {
+        self.count(|x| x.ptr_eq(item)) > 0
+    }
+
+    /// The `relative` value `item` was inserted with, or `None` if this channel does not contain
+    /// it.
+    ///
+    /// If the node was inserted more than once, the value of the insertion emitted first is
+    /// returned. Nodes inserted with [insert_after](Channel::insert_after) or
+    /// [insert_before](Channel::insert_before) have a value of `0`.
+    ///
+    /// ```
+    /// use revent::{Channel, Node};
+    ///
+    /// let channel = Channel::new();
+    ///
+    /// let node = Node::new(0);
+    /// channel.insert(5, node.clone());
+    /// channel.insert(-3, node.clone());
+    ///
+    /// assert_eq!(channel.priority_of(&node), Some(-3));
+    /// assert_eq!(channel.priority_of(&Node::new(0)), None);
+    /// ```
+    pub fn priority_of(&self, item: &Node<T>) -> Option<isize> {
+        self.snapshot()
+            .into_iter()
+            .find(|(_, x)| Node::ptr_eq(x, item))
+            .map(|(id, _)| id.relative)
+    }
+
+    /// Iterate over the `relative` values and nodes of this channel in emission order.
+    ///
+    /// The iterator works on a snapshot, so the channel can be changed while iterating.
+    ///
+    /// ```
+    /// use revent::{Channel, Node};
+    ///
+    /// let channel = Channel::new();
+    ///
+    /// channel.insert(1, Node::new('b'));
+    /// channel.insert(0, Node::new('a'));
+    ///
+    /// let items = channel
+    ///     .iter()
+    ///     .map(|(relative, node)| (relative, node.emit(|x| *x)))
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(items, [(0, 'a'), (1, 'b')]);
+    /// ```
+    pub fn iter(&self) -> impl Iterator<Item = (isize, Node<T>)> {
+        self.snapshot()
+            .into_iter()
+            .map(|(id, node)| (id.relative, node))
+    }
+
+    /// Remove all nodes from this channel.
+    pub fn clear(&self) {
+        self.change(Change::Clear);
+    }
+
+    /// Remove all nodes from this channel, returning them in emission order along with their
+    /// `relative` values.
+    pub fn drain(&self) -> impl Iterator<Item = (isize, Node<T>)> {
+        let items = self.iter().collect::<Vec<_>>();
+        self.clear();
+        items.into_iter()
+    }
+
+    /// Retain only the nodes for which `keep` returns true, removing each other insertion.
+    ///
+    /// ```
+    /// use revent::{Channel, Node};
+    ///
+    /// let channel = Channel::new();
+    ///
+    /// for number in 0..6 {
+    ///     channel.insert(0, Node::new(number));
+    /// }
+    ///
+    /// channel.retain(|node| node.emit(|x| *x % 2 == 0));
+    /// assert_eq!(channel.emit_map(|x| *x), [0, 2, 4]);
+    /// ```
+    pub fn retain(&self, mut keep: impl FnMut(&Node<T>) -> bool) {
+        for (id, node) in self.snapshot() {
+            if !(keep)(&node) {
+                self.detach(id);
+            }
+        }
+        self.apply();
+    }
+
+    // Marks insertion `id` for removal by the next flush, returns its node if it was still
+    // inserted.
+    fn detach(&self, id: SubscriptionId) -> Option<Node<T>> {
+        // unsafe: No mutable reference to `items` exists outside of `flush`, which does not run
+        // user code while holding it.
+        let items = unsafe { &*self.items.get() };
+        let pending = self.pending.borrow();
+        let entry = items.get(&id).or_else(|| {
+            pending.iter().find_map(|x| match x {
+                Change::Insert(key, entry) if *key == id => Some(entry),
+                _ => None,
+            })
+        })?;
+
+        if !entry.is_active() || entry.expired.replace(true) {
+            return None;
+        }
+        self.expired.borrow_mut().push(id);
+        entry.node.upgrade()
+    }
+
+    // Number of nodes matching `filter` this channel contains with all deferred changes applied.
+    fn count(&self, filter: impl Fn(&Link<T>) -> bool) -> usize {
+        // unsafe: No mutable reference to `items` exists outside of `flush`, which does not run
+        // user code while holding it. Neither does this function.
+        let items = unsafe { &*self.items.get() };
+        let pending = self.pending.borrow();
+
+        // Entries queued before the last `Clear` are removed, as are entries followed by a
+        // `Remove` of their node. Positions count `items` as 0 and pending changes from 1.
+        let start = pending
+            .iter()
+            .rposition(|x| matches!(x, Change::Clear))
+            .map_or(0, |x| x + 1);
+        let removals = pending
+            .iter()
+            .enumerate()
+            .filter_map(|(index, x)| match x {
+                Change::Remove(node) => Some((index + 1, node)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let existing = items.values().filter(|_| start == 0).map(|x| (0, x));
+        let inserted = pending
+            .iter()
+            .enumerate()
+            .skip(start)
+            .filter_map(|(index, x)| match x {
+                Change::Insert(_, entry) => Some((index + 1, entry)),
+                _ => None,
+            });
+
+        existing
+            .chain(inserted)
+            .filter(|(_, x)| x.is_active() && !x.expired.get() && x.node.is_alive())
+            .filter(|(position, x)| {
+                !removals
+                    .iter()
+                    .any(|(removal, node)| removal > position && x.node.ptr_eq(node))
+            })
+            .filter(|(_, x)| filter(&x.node))
+            .count()
+    }
+
+    // Nodes of this channel in emission order, with all deferred changes applied.
+    fn snapshot(&self) -> Vec<(SubscriptionId, Node<T>)> {
+        let mut nodes = BTreeMap::new();
+        {
+            // unsafe: No mutable reference to `items` exists outside of `flush`, which does not
+            // run user code while holding it. Neither does this block.
+            let items = unsafe { &*self.items.get() };
+            let pending = self.pending.borrow();
+
+            let live = |(id, entry): (&SubscriptionId, &Entry<T>)| {
+                if !entry.is_active() || entry.expired.get() {
+                    return None;
+                }
+                let node = entry.node.upgrade()?;
+                Some((*id, (node, entry.constraint)))
+            };
+
+            nodes.extend(items.iter().filter_map(live));
+            for change in pending.iter() {
+                match change {
+                    Change::Insert(id, entry) => nodes.extend(live((id, entry))),
+                    Change::Remove(node) => nodes.retain(|_, (x, _)| !Node::ptr_eq(x, node)),
+                    Change::Clear => nodes.clear(),
+                }
+            }
+        }
+
+        if self.constrained.get() {
+            let listeners = nodes
+                .iter()
+                .map(|(id, (node, constraint))| Listener {
+                    id: *id,
+                    name: node.name(),
+                    label: node.label(),
+                    constraint: *constraint,
+                })
+                .collect();
+            if let Ok(order) = order::sort(listeners) {
+                return order
+                    .into_iter()
+                    .filter_map(|id| nodes.remove(&id).map(|(node, _)| (id, node)))
+                    .collect();
+            }
+        }
+
+        nodes
+            .into_iter()
+            .map(|(id, (node, _))| (id, node))
+            .collect()
+    }
+
+    fn next_id(&self, relative: isize) -> SubscriptionId {
+        let sequence = self.sequence.get() + 1;
+        self.sequence.set(sequence);
+        let sequence = if relative >= 0 { sequence } else { -sequence };
+        SubscriptionId { relative, sequence }
+    }
+
+    // Records a dropped weak node so it can be removed after the current emission.
+    fn expire(&self, id: SubscriptionId, item: &Entry<T>) {
+        if !item.expired.replace(true) {
+            self.expired.borrow_mut().push(id);
+        }
+    }
+
+    // Fails if inserting `entry` would make the ordering constraints contradict each other.
+    //
+    // Only constrained entries need to be checked. An unconstrained node can only be preceded by
+    // `Before` entries and followed by `After` entries, and `After` entries are only ever
+    // followed by other `After` entries, so it can not be part of a cycle.
+    fn check_order(&self, id: SubscriptionId, entry: &Entry<T>) -> Result<(), Error> {
+        // unsafe: No mutable reference to `items` exists outside of `flush`, which does not run
+        // user code while holding it.
+        let items = unsafe { &*self.items.get() };
+        let pending = self.pending.borrow();
+        let inserted = pending.iter().filter_map(|x| match x {
+            Change::Insert(id, entry) => Some((id, entry)),
+            Change::Remove(_) | Change::Clear => None,
+        });
+
+        let listeners = items
+            .iter()
+            .chain(inserted)
+            .chain(Some((&id, entry)))
+            .map(|(id, entry)| entry.listener(*id))
+            .collect::<Vec<_>>();
+        order::check(&listeners, listeners.len() - 1)
+    }
+
+    fn change(&self, change: Change<T>) {
+        self.pending.borrow_mut().push(change);
+        self.apply();
+    }
+
+    // Applies deferred changes right away if no emission is active.
+    fn apply(&self) {
+        if self.emitting.get() == 0 {
+            drop(Emitting::new(self));
+        }
+    }
+
+    // Applies all deferred changes. Only called by the outermost `Emitting` guard.
+    //
+    // Dropping removed nodes may run arbitrary code which in turn uses this channel. The channel
+    // is still marked as emitting, so such changes are deferred and picked up by the loop, and
+    // nodes are only dropped outside of the mutable borrow of `items`.
+    fn flush(&self) {
+        loop {
+            let pending = mem::take(&mut *self.pending.borrow_mut());
+            let expired = mem::take(&mut *self.expired.borrow_mut());
+            if pending.is_empty() && expired.is_empty() {
+                break;
+            }
+
+            let mut removed = Vec::new();
+            {
+                // unsafe: The outermost emission has ended, so no shared references to `items`
+                // exist. No user code runs until this borrow ends.
+                let items = unsafe { &mut *self.items.get() };
+
+                for change in pending {
+                    match change {
+                        Change::Insert(id, entry) => {
+                            items.insert(id, entry);
+                        }
+                        Change::Clear => {
+                            removed.extend(mem::take(items).into_values());
+                        }
+                        Change::Remove(node) => {
+                            let ids = items
+                                .iter()
+                                .filter(|(_, x)| !x.node.is_alive() || x.node.ptr_eq(&node))
+                                .map(|(id, _)| *id)
+                                .collect::<Vec<_>>();
+                            removed.extend(ids.iter().filter_map(|id| items.remove(id)));
+                            removed.push(Entry::new(Link::Strong(node), None));
+                        }
+                    }
+                }
+
+                removed.extend(expired.iter().filter_map(|id| items.remove(id)));
+            }
+            // Sorted by the next emission, so a series of insertions only sorts once.
+            self.stale.set(self.constrained.get());
+            drop(removed);
+        }
     }
 
     /// Apply a function to each item in this channel.
-    pub fn emit(&self, mut handler: impl FnMut(&mut T)) {
-        self.trace.log();
-        Trace::indent();
+    ///
+    /// # Panics #
+    ///
+    /// Panics if any node in this channel is already borrowed.
+    pub fn emit(&self, handler: impl FnMut(&mut T)) {
+        if let Err(error) = self.try_emit(handler) {
+            panic!(
+                "revent: emit: {} (in channel {}){}",
+                error,
+                self.label(),
+                stack_dump(&error)
+            );
+        }
+    }
+
+    /// Apply a function to each item in this channel, returning an [Error] instead of
+    /// panicking.
+    ///
+    /// Emission stops at the first node that is already borrowed, nodes before it will have
+    /// been visited.
+    pub fn try_emit(&self, mut handler: impl FnMut(&mut T)) -> Result<(), Error> {
+        self.dispatch(|x| {
+            (handler)(x);
+            ControlFlow::<Infallible>::Continue(())
+        })
+        .map(|_| ())
+    }
+
+    /// Apply a function to a shared reference of each item in this channel.
+    ///
+    /// See [Node::emit_ref]. Items may be emitted shared again from within `handler`, for
+    /// instance by emitting this channel recursively, without suspending.
+    ///
+    /// ```
+    /// use revent::{Channel, Node};
+    ///
+    /// let channel = Channel::new();
+    ///
+    /// for number in 1..4 {
+    ///     channel.insert(0, Node::new(number));
+    /// }
+    ///
+    /// let mut sum = 0;
+    /// channel.emit_ref(|x| {
+    ///     channel.emit_ref(|y| sum += *x * *y);
+    /// });
+    /// assert_eq!(sum, 36);
+    /// ```
+    ///
+    /// # Panics #
+    ///
+    /// Panics if any node in this channel is mutably borrowed without being suspended.
+    pub fn emit_ref(&self, handler: impl FnMut(&T)) {
+        if let Err(error) = self.try_emit_ref(handler) {
+            panic!(
+                "revent: emit: {} (in channel {}){}",
+                error,
+                self.label(),
+                stack_dump(&error)
+            );
+        }
+    }
 
-        for item in self.items.iter() {
-            item.emit(|x| {
+    /// Apply a function to a shared reference of each item in this channel, returning an
+    /// [Error] instead of panicking.
+    ///
+    /// Emission stops at the first node that is mutably borrowed, nodes before it will have
+    /// been visited.
+    pub fn try_emit_ref(&self, mut handler: impl FnMut(&T)) -> Result<(), Error> {
+        self.visit(|node| {
+            node.try_emit_ref(|x| {
                 (handler)(x);
-            });
+                ControlFlow::<Infallible>::Continue(())
+            })
+        })
+        .map(|_| ())
+    }
+
+    /// Apply a function to each item in this channel until it returns [ControlFlow::Break].
+    ///
+    /// Returns the `Break` value of the handler that stopped emission, or
+    /// [ControlFlow::Continue] if every item was visited.
+    ///
+    /// ```
+    /// use revent::{Channel, Node};
+    /// use std::ops::ControlFlow;
+    ///
+    /// let channel = Channel::new();
+    ///
+    /// for number in 0..10 {
+    ///     channel.insert(0, Node::new(number));
+    /// }
+    ///
+    /// let consumed = channel.emit_until(|x| {
+    ///     if *x == 3 {
+    ///         ControlFlow::Break(*x)
+    ///     } else {
+    ///         ControlFlow::Continue(())
+    ///     }
+    /// });
+    ///
+    /// assert_eq!(consumed, ControlFlow::Break(3));
+    /// ```
+    ///
+    /// # Panics #
+    ///
+    /// Panics if any visited node in this channel is already borrowed.
+    pub fn emit_until<B>(&self, handler: impl FnMut(&mut T) -> ControlFlow<B>) -> ControlFlow<B> {
+        match self.dispatch(handler) {
+            Ok(flow) => flow,
+            Err(error) => panic!(
+                "revent: emit: {} (in channel {}){}",
+                error,
+                self.label(),
+                stack_dump(&error)
+            ),
         }
+    }
 
-        Trace::dedent();
+    /// Apply a function to each item in this channel and collect the results.
+    ///
+    /// ```
+    /// use revent::{Channel, Node};
+    ///
+    /// let channel = Channel::new();
+    ///
+    /// for number in 0..3 {
+    ///     channel.insert(0, Node::new(number));
+    /// }
+    ///
+    /// assert_eq!(channel.emit_map(|x| *x * 2), [0, 2, 4]);
+    /// ```
+    ///
+    /// # Panics #
+    ///
+    /// Panics if any node in this channel is already borrowed.
+    pub fn emit_map<R>(&self, handler: impl FnMut(&mut T) -> R) -> Vec<R> {
+        self.emit_iter(handler).collect()
+    }
+
+    /// Apply a function to each item in this channel, threading an accumulator through each
+    /// call.
+    ///
+    /// Useful for asking every item a question, for instance whether a window may be closed.
+    ///
+    /// ```
+    /// use revent::{Channel, Node};
+    ///
+    /// let channel = Channel::new();
+    ///
+    /// channel.insert(0, Node::new(true));
+    /// channel.insert(0, Node::new(false));
+    ///
+    /// let can_close = channel.emit_fold(true, |acc, x| acc && *x);
+    /// assert!(!can_close);
+    /// ```
+    ///
+    /// # Panics #
+    ///
+    /// Panics if any node in this channel is already borrowed.
+    pub fn emit_fold<A>(&self, init: A, mut handler: impl FnMut(A, &mut T) -> A) -> A {
+        let mut accumulator = Some(init);
+        self.emit(|x| {
+            // The accumulator is always put back before the next item is visited.
+            let value = accumulator.take().unwrap();
+            accumulator = Some((handler)(value, x));
+        });
+        accumulator.unwrap()
+    }
+
+    /// Lazily apply a function to each item in this channel.
+    ///
+    /// Each call to `next` on the returned iterator emits on the next item, which allows
+    /// stopping early or interleaving emissions with other work. Every such step is traced as a
+    /// separate emission of this channel.
+    ///
+    /// ```
+    /// use revent::{Channel, Node};
+    ///
+    /// let channel = Channel::new();
+    ///
+    /// for number in 0..10 {
+    ///     channel.insert(0, Node::new(number));
+    /// }
+    ///
+    /// let second_even = channel.emit_iter(|x| *x).filter(|x| x % 2 == 0).nth(1);
+    /// assert_eq!(second_even, Some(2));
+    /// ```
+    ///
+    /// # Panics #
+    ///
+    /// The iterator panics if the node it visits is already borrowed.
+    pub fn emit_iter<'a, R: 'a>(
+        &'a self,
+        handler: impl FnMut(&mut T) -> R + 'a,
+    ) -> impl Iterator<Item = R> + 'a {
+        self.trace.log();
+        let emitting = Emitting::new(self);
+
+        EmitIter {
+            // unsafe: `items` is not mutated while `emitting` is alive.
+            items: self.entries(),
+            handler,
+            emitting,
+        }
+    }
+
+    fn indent(&self) -> Indent {
+        let address: *const Self = self;
+        Trace::indent(
+            TraceKind::Channel,
+            address as *const () as usize,
+            self.name,
+            any::type_name::<T>(),
+        )
+    }
+
+    fn label(&self) -> &'static str {
+        self.name.unwrap_or_else(any::type_name::<T>)
+    }
+
+    // Items in emission order, must only be called while emitting.
+    fn entries(&self) -> Entries<'_, T> {
+        // unsafe: `items` is not mutated while an `Emitting` guard is alive.
+        let items = unsafe { &*self.items.get() };
+        if self.stale.replace(false) {
+            // unsafe: `stale` is only set by `flush`, after the outermost emission ended. This is
+            // the first call since, so no `Entries` borrowing `order` exists. No user code runs
+            // while sorting.
+            let order = unsafe { &mut *self.order.get() };
+            let listeners = items.iter().map(|(id, x)| x.listener(*id)).collect();
+            // Every insertion was checked for cycles, so sorting does not fail.
+            *order = order::sort(listeners).ok();
+        }
+        // unsafe: See above, `order` is only mutated while no `Entries` exist.
+        match unsafe { &*self.order.get() } {
+            Some(order) => Entries::Ordered(items, order.iter()),
+            None => Entries::Sorted(items.iter()),
+        }
+    }
+
+    fn dispatch<B>(
+        &self,
+        mut handler: impl FnMut(&mut T) -> ControlFlow<B>,
+    ) -> Result<ControlFlow<B>, Error> {
+        self.visit(|node| node.try_emit(&mut handler))
+    }
+
+    // Emits every active node with `emit`, which returns `None` for dropped weak nodes.
+    fn visit<B>(
+        &self,
+        mut emit: impl FnMut(&Link<T>) -> Option<Result<ControlFlow<B>, Error>>,
+    ) -> Result<ControlFlow<B>, Error> {
+        self.trace.log();
+        let _indent = self.indent();
+        let _emitting = Emitting::new(self);
+
+        for (id, item) in self.entries().filter(|(_, x)| x.is_active()) {
+            match (emit)(&item.node) {
+                Some(Ok(ControlFlow::Continue(()))) => {}
+                Some(Ok(flow)) => return Ok(flow),
+                Some(Err(error)) => return Err(error),
+                None => self.expire(*id, item),
+            }
+        }
+
+        Ok(ControlFlow::Continue(()))
+    }
+}
+
+impl<T: ?Sized> Attach for Channel<T> {
+    type Item = T;
+
+    /// Subscribe a node with a `relative` value of `0`.
+    fn attach(&mut self, item: Node<T>) -> Subscription {
+        self.subscribe(0, item)
+    }
+}
+
+impl<T: ?Sized> fmt::Debug for Channel<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Channel")
+            .field("name", &self.name)
+            .field("type_name", &any::type_name::<T>())
+            .field("emitting", &(self.emitting.get() > 0))
+            .finish()
+    }
+}
+
+enum Entries<'a, T: ?Sized> {
+    Sorted(btree_map::Iter<'a, SubscriptionId, Entry<T>>),
+    Ordered(
+        &'a BTreeMap<SubscriptionId, Entry<T>>,
+        slice::Iter<'a, SubscriptionId>,
+    ),
+}
+
+impl<'a, T: ?Sized> Iterator for Entries<'a, T> {
+    type Item = (&'a SubscriptionId, &'a Entry<T>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Entries::Sorted(items) => items.next(),
+            // `order` always lists exactly the keys of `items`.
+            Entries::Ordered(items, order) => order.next().map(|id| (id, &items[id])),
+        }
+    }
+}
+
+struct EmitIter<'a, T: ?Sized, F> {
+    items: Entries<'a, T>,
+    handler: F,
+    // Declared last so that it is dropped after `items`, as dropping it may mutate the channel.
+    emitting: Emitting<'a, T>,
+}
+
+impl<'a, T: ?Sized, F: FnMut(&mut T) -> R, R> Iterator for EmitIter<'a, T, F> {
+    type Item = R;
+
+    fn next(&mut self) -> Option<R> {
+        // Traced per step, so work done between steps is not attributed to this channel.
+        let _indent = self.emitting.channel.indent();
+        for (id, item) in self.items.by_ref().filter(|(_, x)| x.is_active()) {
+            match item.node.try_emit(&mut self.handler) {
+                Some(Ok(value)) => return Some(value),
+                Some(Err(error)) => {
+                    let label = self.emitting.channel.label();
+                    panic!(
+                        "revent: emit: {} (in channel {}){}",
+                        error,
+                        label,
+                        stack_dump(&error)
+                    )
+                }
+                None => self.emitting.channel.expire(*id, item),
+            }
+        }
+        None
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::{Channel, Node};
+    use crate::{Error, Suspend};
+    use std::ops::ControlFlow;
 
     #[test]
     fn removing_considers_order() {
-        let mut channel = Channel::new();
+        let channel = Channel::new();
         let node = Node::new(());
         channel.insert(0, node.clone());
         channel.remove(&node);
@@ -93,7 +1052,7 @@ mod tests {
 
     #[quickcheck_macros::quickcheck]
     fn inserting_appends_or_prepends(relative: isize, nodes: usize) {
-        let mut channel = Channel::new();
+        let channel = Channel::new();
 
         for node in 0..nodes {
             channel.insert(relative, Node::new(node));
@@ -118,7 +1077,7 @@ mod tests {
 
     #[test]
     fn basic() {
-        let mut channel = Channel::new();
+        let channel = Channel::new();
 
         let node = Node::new(0);
         channel.insert(0, node.clone());
@@ -142,9 +1101,351 @@ mod tests {
     }
 
     #[test]
-    fn haystack() {
+    fn try_emit_stops_at_conflict() {
+        let channel = Channel::new();
+
+        let node = Node::new(1);
+        channel.insert(0, Node::new(0));
+        channel.insert(1, node.clone());
+        channel.insert(2, Node::new(2));
+
+        node.emit(|_| {
+            let mut visited = vec![];
+            let result = channel.try_emit(|x| visited.push(*x));
+            assert_eq!(result, Err(Error::AlreadyBorrowed(vec!["i32", "i32"])));
+            assert_eq!(visited, [0]);
+        });
+
+        node.emit(|x| {
+            x.suspend(|| {
+                let mut visited = vec![];
+                assert_eq!(channel.try_emit(|x| visited.push(*x)), Ok(()));
+                assert_eq!(visited, [0, 1, 2]);
+            });
+        });
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "revent: emit: accessing already borrowed item: player -> player (in channel damage)"
+    )]
+    fn emit_names_conflict() {
+        let channel = Channel::named("damage");
+        let player = Node::named("player", 0);
+        channel.insert(0, player.clone());
+
+        assert_eq!(channel.name(), Some("damage"));
+        assert_eq!(
+            format!("{:?}", channel),
+            r#"Channel { name: Some("damage"), type_name: "i32", emitting: false }"#
+        );
+
+        player.emit(|_| channel.emit(|_| {}));
+    }
+
+    #[test]
+    fn emit_ref_excludes_mutable() {
+        let channel = Channel::new();
+        let node = Node::new(1);
+        channel.insert(0, node.clone());
+        channel.insert(1, Node::new(2));
+
+        channel.emit_ref(|x| {
+            assert_eq!(node.try_emit(|_| {}).is_err(), *x == 1);
+            assert_eq!(node.emit_ref(|y| *y), 1);
+        });
+
+        let mut visited = vec![];
+        node.emit(|_| {
+            assert!(channel.try_emit_ref(|x| visited.push(*x)).is_err());
+        });
+        assert!(visited.is_empty());
+    }
+
+    #[test]
+    fn constraints_apply_after_emission() {
+        let channel = Channel::new();
+        channel.insert(0, Node::named("b", 'b'));
+
+        let mut visited = vec![];
+        channel.emit(|_| {
+            channel.insert_before("b", Node::named("a", 'a'));
+            channel.insert_after("a", Node::named("c", 'c'));
+        });
+        channel.emit(|x| visited.push(*x));
+        assert_eq!(visited, ['a', 'b', 'c']);
+
+        channel.insert(-1, Node::new('z'));
+        visited.clear();
+        channel.emit(|x| visited.push(*x));
+        assert_eq!(visited, ['z', 'a', 'b', 'c']);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "revent: insert: ordering cycle: c -> a -> b -> c (in channel cycle)"
+    )]
+    fn constraint_completes_cycle() {
+        let channel = Channel::named("cycle");
+        channel.insert_before("b", Node::named("a", ()));
+        channel.insert_before("c", Node::named("b", ()));
+        channel.insert(0, Node::named("d", ()));
+        channel.insert_before("a", Node::named("c", ()));
+    }
+
+    #[test]
+    fn introspection_sees_deferred_changes() {
+        let channel = Channel::new();
+        let first = Node::named("first", 1);
+        let second = Node::named("second", 2);
+        channel.insert(0, first.clone());
+
+        channel.emit(|_| {
+            channel.insert_before("first", second.clone());
+            assert_eq!(channel.len(), 2);
+            assert_eq!(channel.priority_of(&second), Some(0));
+
+            let order = channel.iter().map(|(_, x)| x.label()).collect::<Vec<_>>();
+            assert_eq!(order, ["second", "first"]);
+
+            channel.retain(|x| Node::ptr_eq(x, &second));
+            assert!(!channel.contains(&first));
+        });
+        assert_eq!(channel.emit_map(|x| *x), [2]);
+
+        channel.emit(|_| {
+            assert_eq!(channel.drain().count(), 1);
+            assert!(channel.is_empty());
+            channel.insert(0, first.clone());
+        });
+        assert_eq!(channel.emit_map(|x| *x), [1]);
+
+        let subscription = channel.subscribe(0, second.clone());
+        assert!(channel.contains(&second));
+        drop(subscription);
+        assert!(!channel.contains(&second));
+        channel.clear();
+        assert!(channel.is_empty());
+    }
+
+    #[test]
+    fn counting_follows_deferred_order() {
+        let channel = Channel::new();
+        let node = Node::new(0);
+        channel.insert(0, node.clone());
+
+        channel.emit(|_| {
+            channel.remove(&node);
+            channel.insert(0, node.clone());
+            assert_eq!(channel.len(), 1);
+            channel.clear();
+            channel.insert(0, Node::new(1));
+            channel.insert(0, node.clone());
+            channel.remove(&node);
+            assert_eq!(channel.len(), 1);
+            assert!(!channel.contains(&node));
+        });
+        assert_eq!(channel.emit_map(|x| *x), [1]);
+    }
+
+    #[test]
+    fn unsubscribe_single_insertion() {
+        let channel = Channel::new();
+
+        let node = Node::new(0);
+        let first = channel.insert(0, node.clone());
+        channel.insert(1, Node::new(1));
+        channel.insert(2, node.clone());
+
+        assert!(channel.unsubscribe(first).is_some());
+        assert!(channel.unsubscribe(first).is_none());
+
+        let mut visited = vec![];
+        channel.emit(|x| visited.push(*x));
+        assert_eq!(visited, [1, 0]);
+    }
+
+    #[test]
+    fn subscription_drop_unsubscribes() {
+        let channel = Channel::new();
+
+        let first = channel.subscribe(0, Node::new(0));
+        let second = channel.subscribe(0, Node::new(1));
+        assert_ne!(first.id(), second.id());
+        assert!(first.id().is_some());
+
+        drop(first);
+
+        let mut visited = vec![];
+        channel.emit(|x| visited.push(*x));
+        assert_eq!(visited, [1]);
+
+        channel.insert(0, Node::new(2));
+        drop(second);
+        drop(channel);
+    }
+
+    #[test]
+    fn weak_expires() {
         let mut channel = Channel::new();
 
+        let node = Node::new(0);
+        channel.insert_weak(0, Node::downgrade(&node));
+        channel.insert(1, Node::new(1));
+
+        let mut visited = vec![];
+        channel.emit(|x| visited.push(*x));
+        assert_eq!(visited, [0, 1]);
+
+        drop(node);
+
+        for _ in 0..2 {
+            let mut visited = vec![];
+            channel.emit(|x| visited.push(*x));
+            assert_eq!(visited, [1]);
+        }
+
+        assert!(channel.expired.borrow().is_empty());
+        assert_eq!(channel.items.get_mut().len(), 1);
+    }
+
+    #[test]
+    fn mutation_during_emit_is_deferred() {
+        let channel = Channel::new();
+
+        let node = Node::new(0);
+        let id = channel.insert(0, node.clone());
+
+        let mut visited = vec![];
+        channel.emit(|x| {
+            channel.insert(1, Node::new(*x + 1));
+            x.suspend(|| {
+                channel.emit(|x| {
+                    channel.remove(&node);
+                    visited.push(*x);
+                });
+            });
+            assert!(channel.unsubscribe(id).is_some());
+            assert!(channel.unsubscribe(id).is_none());
+            visited.push(*x);
+        });
+        assert_eq!(visited, [0, 0]);
+
+        let mut visited = vec![];
+        channel.emit(|x| visited.push(*x));
+        assert_eq!(visited, [1]);
+    }
+
+    #[test]
+    fn unsubscribe_pending_insertion() {
+        let channel = Channel::new();
+        channel.insert(0, Node::new(0));
+
+        channel.emit(|_| {
+            let id = channel.insert(0, Node::new(1));
+            assert!(channel.unsubscribe(id).is_some());
+        });
+
+        let mut visited = vec![];
+        channel.emit(|x| visited.push(*x));
+        assert_eq!(visited, [0]);
+    }
+
+    #[test]
+    fn dropping_removed_node_may_use_channel() {
+        use std::rc::Rc;
+
+        struct Reentrant(Option<Rc<Channel<Reentrant>>>);
+
+        impl Drop for Reentrant {
+            fn drop(&mut self) {
+                if let Some(channel) = self.0.take() {
+                    channel.emit(|_| {});
+                    channel.insert(0, Node::new(Reentrant(None)));
+                }
+            }
+        }
+
+        let channel = Rc::new(Channel::new());
+        let node = Node::new(Reentrant(Some(channel.clone())));
+        channel.insert(0, node.clone());
+
+        channel.remove(&node);
+        drop(node);
+
+        let mut count = 0;
+        channel.emit(|x| {
+            assert!(x.0.is_none());
+            count += 1;
+        });
+        assert_eq!(count, 1);
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn emit_until_stops_at_break(nodes: u8, stop: u8) {
+        let channel = Channel::new();
+
+        for node in 0..nodes {
+            channel.insert(0, Node::new(node));
+        }
+
+        let mut visited = 0;
+        let flow = channel.emit_until(|x| {
+            visited += 1;
+            if *x == stop {
+                ControlFlow::Break(*x)
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+
+        if stop < nodes {
+            assert_eq!(flow, ControlFlow::Break(stop));
+            assert_eq!(visited, usize::from(stop) + 1);
+        } else {
+            assert_eq!(flow, ControlFlow::Continue(()));
+            assert_eq!(visited, usize::from(nodes));
+        }
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn emit_map_and_fold(values: Vec<u8>) {
+        let channel = Channel::new();
+
+        for value in values.iter() {
+            channel.insert(0, Node::new(*value));
+        }
+
+        assert_eq!(channel.emit_map(|x| *x), values);
+
+        let sum = channel.emit_fold(0usize, |acc, x| acc + usize::from(*x));
+        assert_eq!(sum, values.iter().map(|x| usize::from(*x)).sum::<usize>());
+    }
+
+    #[test]
+    fn emit_iter_is_lazy() {
+        let channel = Channel::new();
+
+        for number in 0..10 {
+            channel.insert(0, Node::new(number));
+        }
+
+        let mut visited = 0;
+        let found = channel
+            .emit_iter(|x| {
+                visited += 1;
+                *x
+            })
+            .find(|x| *x == 4);
+
+        assert_eq!(found, Some(4));
+        assert_eq!(visited, 5);
+    }
+
+    #[test]
+    fn haystack() {
+        let channel = Channel::new();
+
         let node = Node::new(0);
         for _ in 0..10 {
             channel.insert(0, node.clone());
@@ -176,7 +1477,7 @@ mod trace_tests {
         let out = Rc::new(RefCell::new(None));
 
         let capture = out.clone();
-        let mut channel = Channel::new_with_trace(move |indent| {
+        let channel = Channel::new_with_trace(move |indent| {
             assert!(matches!(*capture.borrow(), None));
             *capture.borrow_mut() = Some(indent);
         });
@@ -0,0 +1,213 @@
+use crate::{TraceEvent, TraceKind, TracePhase, TraceSink};
+use std::{
+    cell::RefCell,
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+
+/// [TraceSink] recording emissions in the Chrome Trace Event Format.
+///
+/// Every emission of a [Node](crate::Node), [Channel](crate::Channel) and [Slot](crate::Slot),
+/// and every [suspend](crate::Suspend::suspend)ed stretch, becomes a complete event with its
+/// duration, named after the name or otherwise the type name of the node or container. The
+/// resulting JSON can be opened in `chrome://tracing` or [Perfetto](https://ui.perfetto.dev) to
+/// inspect a signal cascade.
+///
+/// ```
+/// use revent::{ChromeTrace, Node};
+/// use std::rc::Rc;
+///
+/// let trace = Rc::new(ChromeTrace::new());
+/// revent::set_trace_sink(Some(trace.clone()));
+///
+/// Node::new(123).emit(|_| {});
+///
+/// revent::set_trace_sink(None);
+/// assert_eq!(trace.len(), 1);
+///
+/// let mut json = Vec::new();
+/// trace.write_json(&mut json).unwrap();
+/// assert!(String::from_utf8(json).unwrap().contains(r#""name":"i32""#));
+/// ```
+pub struct ChromeTrace {
+    start: Instant,
+    state: RefCell<State>,
+}
+
+#[derive(Default)]
+struct State {
+    // Enters awaiting their exit, innermost last.
+    open: Vec<TraceEvent>,
+    spans: Vec<Span>,
+}
+
+struct Span {
+    kind: TraceKind,
+    id: usize,
+    name: Option<&'static str>,
+    type_name: &'static str,
+    depth: usize,
+    start: Duration,
+    duration: Duration,
+}
+
+impl Default for ChromeTrace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ChromeTrace {
+    /// Create an empty recording, timestamps are relative to this call.
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            state: RefCell::new(State::default()),
+        }
+    }
+
+    /// Number of completed events recorded.
+    pub fn len(&self) -> usize {
+        self.state.borrow().spans.len()
+    }
+
+    /// Returns true if no events have completed yet.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Discard all recorded events.
+    pub fn clear(&self) {
+        let mut state = self.state.borrow_mut();
+        state.open.clear();
+        state.spans.clear();
+    }
+
+    /// Write the completed events as a Chrome Trace Event Format JSON object.
+    ///
+    /// Operations which have not exited yet are omitted.
+    pub fn write_json(&self, mut writer: impl Write) -> io::Result<()> {
+        let state = self.state.borrow();
+
+        write!(writer, r#"{{"displayTimeUnit":"ns","traceEvents":["#)?;
+        for (index, span) in state.spans.iter().enumerate() {
+            if index > 0 {
+                write!(writer, ",")?;
+            }
+            write!(writer, r#"{{"name":""#)?;
+            write_escaped(&mut writer, span.name.unwrap_or(span.type_name))?;
+            write!(writer, r#"","args":{{"type_name":""#)?;
+            write_escaped(&mut writer, span.type_name)?;
+            write!(
+                writer,
+                r#"","id":"{:#x}","depth":{}}},"cat":"{}","ph":"X","ts":{:.3},"dur":{:.3},"pid":1,"tid":1}}"#,
+                span.id,
+                span.depth,
+                category(span.kind),
+                span.start.as_secs_f64() * 1e6,
+                span.duration.as_secs_f64() * 1e6,
+            )?;
+        }
+        write!(writer, "]}}")
+    }
+}
+
+impl TraceSink for ChromeTrace {
+    fn event(&self, event: &TraceEvent) {
+        let mut state = self.state.borrow_mut();
+        match event.phase {
+            TracePhase::Enter => state.open.push(*event),
+            TracePhase::Exit => {
+                // Exits of operations entered before this sink was installed have no enter.
+                let enter = match state.open.pop() {
+                    Some(enter) if enter.id == event.id => enter,
+                    Some(enter) => {
+                        state.open.push(enter);
+                        return;
+                    }
+                    None => return,
+                };
+                let start = enter.timestamp.saturating_duration_since(self.start);
+                state.spans.push(Span {
+                    kind: enter.kind,
+                    id: enter.id,
+                    name: enter.name,
+                    type_name: enter.type_name,
+                    depth: enter.depth,
+                    start,
+                    duration: event.timestamp.saturating_duration_since(enter.timestamp),
+                });
+            }
+        }
+    }
+}
+
+fn category(kind: TraceKind) -> &'static str {
+    match kind {
+        TraceKind::Node => "node",
+        TraceKind::Slot => "slot",
+        TraceKind::Channel => "channel",
+        TraceKind::Suspend | TraceKind::Resume => "suspend",
+    }
+}
+
+fn write_escaped(writer: &mut impl Write, string: &str) -> io::Result<()> {
+    for character in string.chars() {
+        match character {
+            '"' => write!(writer, "\\\"")?,
+            '\\' => write!(writer, "\\\\")?,
+            character if character.is_control() => write!(writer, "\\u{:04x}", character as u32)?,
+            character => write!(writer, "{}", character)?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use std::rc::Rc;
+
+    #[test]
+    fn records_nested_spans() {
+        let trace = Rc::new(ChromeTrace::new());
+
+        let channel = Channel::new();
+        let node = Node::new(());
+        channel.insert(0, node.clone());
+
+        channel.emit(|_| {
+            // Exits without a recorded enter are ignored.
+            set_trace_sink(Some(trace.clone()));
+        });
+        assert!(trace.is_empty());
+
+        channel.emit(|x| {
+            x.suspend(|| {});
+        });
+        set_trace_sink(None);
+        assert_eq!(trace.len(), 3);
+
+        let mut json = Vec::new();
+        trace.write_json(&mut json).unwrap();
+        let json = String::from_utf8(json).unwrap();
+
+        assert!(json.starts_with(r#"{"displayTimeUnit":"ns","traceEvents":[{"#));
+        assert!(json.ends_with("}]}"));
+        assert_eq!(json.matches(r#""ph":"X""#).count(), 3);
+        for category in &["channel", "node", "suspend"] {
+            assert!(json.contains(&format!(r#""cat":"{}""#, category)));
+        }
+        assert!(json.contains(r#""name":"()""#));
+
+        trace.clear();
+        assert!(trace.is_empty());
+    }
+
+    #[test]
+    fn escapes_names() {
+        let mut json = Vec::new();
+        super::write_escaped(&mut json, "a\"b\\c\n").unwrap();
+        assert_eq!(json, br#"a\"b\\c\u000a"#);
+    }
+}
@@ -0,0 +1,87 @@
+use std::fmt;
+
+/// Error returned by the non-panicking `try_*` variants of emit and suspend.
+///
+/// Each variant corresponds to a situation in which the panicking counterpart would panic.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Error {
+    /// The node is already borrowed and has not been [suspend](crate::Suspend::suspend)ed.
+    ///
+    /// Contains the [label](crate::Node::label)s of all emitted nodes, outermost first, ending
+    /// with the node that failed to emit. Panics caused by this error also list the
+    /// [current_stack](crate::current_stack).
+    AlreadyBorrowed(Vec<&'static str>),
+    /// Suspend was called while no node is being emitted.
+    NotInNodeContext,
+    /// Suspend was called on an object that is not the last emitted node.
+    ///
+    /// Contains the label of the last emitted node and the type name of the object that was to
+    /// be suspended.
+    NotTopOfStack {
+        /// Label of the node at the top of the stack.
+        expected: &'static str,
+        /// Type name of the object suspend was called on.
+        actual: &'static str,
+    },
+    /// The [Slot](crate::Slot) contains no node.
+    EmptySlot,
+    /// The [Slot](crate::Slot) already contains a node.
+    OccupiedSlot,
+    /// Emitting would exceed the [maximum depth](crate::set_max_depth) of nested emissions.
+    ///
+    /// Contains the labels of all emitted nodes, outermost first, ending with the node that
+    /// failed to emit.
+    DepthLimit(Vec<&'static str>),
+    /// Emitting would exceed the [reentrancy limit](crate::Node::set_reentrancy_limit) of the
+    /// node.
+    ///
+    /// Contains the labels of the emitted nodes forming the cycle, starting and ending with the
+    /// node that failed to emit.
+    ReentrancyLimit(Vec<&'static str>),
+    /// The [SyncNode](crate::SyncNode) is being emitted by another thread.
+    ///
+    /// Contains the labels of all nodes emitted by this thread, outermost first, ending with the
+    /// node that failed to emit.
+    HeldByOtherThread(Vec<&'static str>),
+    /// Inserting into a [Channel](crate::Channel) would create a cycle between
+    /// [insert_after](crate::Channel::insert_after) and
+    /// [insert_before](crate::Channel::insert_before) constraints.
+    ///
+    /// Contains the labels of the nodes forming the cycle, starting and ending with the same
+    /// node.
+    OrderingCycle(Vec<&'static str>),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::AlreadyBorrowed(chain) => {
+                write!(f, "accessing already borrowed item: {}", chain.join(" -> "))
+            }
+            Error::NotInNodeContext => write!(f, "not inside node context"),
+            Error::NotTopOfStack { expected, actual } => {
+                write!(
+                    f,
+                    "item not expected: {} (top of stack is {})",
+                    actual, expected
+                )
+            }
+            Error::EmptySlot => write!(f, "slot contains no element"),
+            Error::OccupiedSlot => write!(f, "slot already contains an element"),
+            Error::DepthLimit(chain) => {
+                write!(f, "depth limit exceeded: {}", chain.join(" -> "))
+            }
+            Error::ReentrancyLimit(chain) => {
+                write!(f, "reentrancy limit exceeded: {}", chain.join(" -> "))
+            }
+            Error::HeldByOtherThread(chain) => {
+                write!(f, "item held by another thread: {}", chain.join(" -> "))
+            }
+            Error::OrderingCycle(chain) => {
+                write!(f, "ordering cycle: {}", chain.join(" -> "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
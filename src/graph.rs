@@ -0,0 +1,197 @@
+use crate::{TraceEvent, TraceKind, TracePhase, TraceSink};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet},
+    io::{self, Write},
+};
+
+/// [TraceSink] aggregating which nodes emit which channels and slots, and which nodes those
+/// reach.
+///
+/// Records an edge from the emitting node to a [Channel](crate::Channel) or
+/// [Slot](crate::Slot), and from the container to every node it reaches, counting how often
+/// each edge is taken. Vertices are identified by their kind and their name, or otherwise their
+/// type name, so all unnamed nodes of one type share a vertex. The result is written in the
+/// Graphviz DOT format.
+///
+/// ```
+/// use revent::{CallGraph, Channel, Node};
+/// use std::rc::Rc;
+///
+/// let graph = Rc::new(CallGraph::new());
+/// revent::set_trace_sink(Some(graph.clone()));
+///
+/// let channel = Channel::new();
+/// channel.insert(0, Node::new(123));
+/// channel.emit(|_| {});
+/// channel.emit(|_| {});
+///
+/// revent::set_trace_sink(None);
+/// assert_eq!(graph.count(("channel", "i32"), ("node", "i32")), 2);
+///
+/// let mut dot = Vec::new();
+/// graph.write_dot(&mut dot).unwrap();
+/// assert!(String::from_utf8(dot).unwrap().starts_with("digraph revent {"));
+/// ```
+pub struct CallGraph {
+    state: RefCell<State>,
+}
+
+/// Vertex of a [CallGraph], a category (`node`, `channel` or `slot`) and a name or type name.
+pub type Vertex = (&'static str, &'static str);
+
+#[derive(Default)]
+struct State {
+    // Operations entered but not yet exited, innermost last.
+    open: Vec<(usize, Option<Vertex>)>,
+    vertices: BTreeSet<Vertex>,
+    edges: BTreeMap<(Vertex, Vertex), usize>,
+}
+
+impl Default for CallGraph {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CallGraph {
+    /// Create an empty graph.
+    pub fn new() -> Self {
+        Self {
+            state: RefCell::new(State::default()),
+        }
+    }
+
+    /// Number of times `from` was observed emitting `to`.
+    pub fn count(&self, from: Vertex, to: Vertex) -> usize {
+        self.state
+            .borrow()
+            .edges
+            .get(&(from, to))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Discard all recorded vertices and edges.
+    pub fn clear(&self) {
+        let mut state = self.state.borrow_mut();
+        state.open.clear();
+        state.vertices.clear();
+        state.edges.clear();
+    }
+
+    /// Write the graph in the Graphviz DOT format, with edges labelled by their counts.
+    pub fn write_dot(&self, mut writer: impl Write) -> io::Result<()> {
+        let state = self.state.borrow();
+
+        let indices = state
+            .vertices
+            .iter()
+            .enumerate()
+            .map(|(index, vertex)| (*vertex, index))
+            .collect::<BTreeMap<_, _>>();
+
+        writeln!(writer, "digraph revent {{")?;
+        for (index, (category, label)) in state.vertices.iter().enumerate() {
+            let shape = match *category {
+                "node" => "box",
+                "slot" => "diamond",
+                _ => "ellipse",
+            };
+            writeln!(
+                writer,
+                "    v{} [label={:?}, shape={}];",
+                index, label, shape
+            )?;
+        }
+        for ((from, to), count) in &state.edges {
+            writeln!(
+                writer,
+                "    v{} -> v{} [label=\"{}\"];",
+                indices[from], indices[to], count
+            )?;
+        }
+        writeln!(writer, "}}")
+    }
+}
+
+impl TraceSink for CallGraph {
+    fn event(&self, event: &TraceEvent) {
+        let mut state = self.state.borrow_mut();
+        let state = &mut *state;
+
+        match event.phase {
+            TracePhase::Enter => {
+                let label = event.name.unwrap_or(event.type_name);
+                let vertex = match event.kind {
+                    TraceKind::Node => Some(("node", label)),
+                    TraceKind::Slot => Some(("slot", label)),
+                    TraceKind::Channel => Some(("channel", label)),
+                    TraceKind::Suspend | TraceKind::Resume => None,
+                };
+
+                if let Some(vertex) = vertex {
+                    // Suspensions are transparent, the caller is the innermost node or container.
+                    let caller = state.open.iter().rev().find_map(|(_, x)| *x);
+                    if let Some(caller) = caller {
+                        *state.edges.entry((caller, vertex)).or_insert(0) += 1;
+                    }
+                    state.vertices.insert(vertex);
+                }
+                state.open.push((event.id, vertex));
+            }
+            TracePhase::Exit => {
+                // Exits of operations entered before this sink was installed have no enter.
+                if matches!(state.open.last(), Some((id, _)) if *id == event.id) {
+                    state.open.pop();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use std::rc::Rc;
+
+    #[test]
+    fn records_edges_through_suspend() {
+        let graph = Rc::new(CallGraph::new());
+        set_trace_sink(Some(graph.clone()));
+
+        let channel = Channel::new();
+        let mut slot = Slot::new();
+        slot.insert(Node::new(0u16));
+        channel.insert(0, Node::new(0u8));
+        channel.insert(0, Node::new(1u8));
+
+        channel.emit(|x| {
+            x.suspend(|| {
+                slot.emit(|_| {});
+            });
+        });
+        set_trace_sink(None);
+
+        assert_eq!(graph.count(("channel", "u8"), ("node", "u8")), 2);
+        assert_eq!(graph.count(("node", "u8"), ("slot", "u16")), 2);
+        assert_eq!(graph.count(("slot", "u16"), ("node", "u16")), 2);
+        assert_eq!(graph.count(("node", "u8"), ("node", "u16")), 0);
+
+        let mut dot = Vec::new();
+        graph.write_dot(&mut dot).unwrap();
+        assert_eq!(
+            String::from_utf8(dot).unwrap(),
+            "digraph revent {
+    v0 [label=\"u8\", shape=ellipse];
+    v1 [label=\"u16\", shape=box];
+    v2 [label=\"u8\", shape=box];
+    v3 [label=\"u16\", shape=diamond];
+    v0 -> v2 [label=\"2\"];
+    v2 -> v3 [label=\"2\"];
+    v3 -> v1 [label=\"2\"];
+}
+"
+        );
+    }
+}
@@ -53,6 +53,25 @@
 //!
 //! The above allows the object to emit a signal on a channel it is part of, even calling itself
 //! recursively without mutably aliasing by suspending `&mut self`.
+//!
+//! # Threads #
+//!
+//! [Node], [Channel] and [Slot] are confined to the thread that created them. [SyncNode],
+//! [SyncChannel] and [SyncSlot] can be shared between threads, a sync node is owned by the thread
+//! emitting it until that emission returns. With the `rayon` feature,
+//! `SyncChannel::par_emit` visits the nodes of a channel in parallel.
+//!
+//! # Nightly #
+//!
+//! On stable Rust a `Node<A>` is turned into a `Node<dyn MyTrait>` using [node_dyn], and a
+//! [SyncNode] using [sync_node_dyn]. Enabling the `nightly` feature implements `CoerceUnsized`
+//! for [Node], [WeakNode] and [SyncNode], allowing this conversion to happen implicitly.
+//!
+//! # Tracing #
+//!
+//! The `trace` feature delivers structured events to a thread-local sink, see `set_trace_sink`.
+//! The `tracing` feature opens spans of the [tracing](https://docs.rs/tracing) crate for every
+//! emission and suspension, carrying the type name and address of the node or container.
 #![deny(
     missing_docs,
     trivial_casts,
@@ -60,20 +79,66 @@
     unused_import_braces,
     unused_qualifications
 )]
-#![feature(coerce_unsized, drain_filter, unsize)]
+#![cfg_attr(feature = "nightly", feature(coerce_unsized, unsize))]
+// `Option::is_none_or` would require Rust 1.82.
+#![allow(clippy::unnecessary_map_or)]
+// The tests predate these lints and are kept as written.
+#![cfg_attr(
+    test,
+    allow(
+        redundant_semicolons,
+        clippy::redundant_pattern_matching,
+        clippy::unit_arg
+    )
+)]
+
+// Allows the code generated by the derive macros to refer to `::revent` in tests of this crate.
+#[cfg(all(test, feature = "derive"))]
+extern crate self as revent;
 
 use self::trace::Trace;
-pub use self::{channel::Channel, node::Node, slot::Slot};
+#[cfg(not(feature = "trace"))]
+use self::trace::TraceKind;
+#[cfg(feature = "trace")]
+pub use self::trace::{set_trace_sink, TraceEvent, TraceKind, TracePhase, TraceSink};
+pub use self::{
+    channel::Channel,
+    error::Error,
+    node::{Node, Upcast, WeakNode},
+    queue::Queue,
+    slot::Slot,
+    subscription::{Attach, Subscriber, Subscription, SubscriptionId, Subscriptions},
+    sync::{SyncChannel, SyncNode, SyncSlot},
+};
+#[cfg(feature = "trace")]
+pub use self::{
+    chrome::ChromeTrace,
+    graph::{CallGraph, Vertex},
+};
 use std::{
+    any::{self, TypeId},
     cell::{Cell, UnsafeCell},
+    marker::PhantomData,
     mem,
 };
 
+#[cfg(feature = "derive")]
+pub use revent_derive::{signal, Hub, Subscriber};
+
 #[cfg(feature = "asynchronous")]
 pub mod asynchronous;
 mod channel;
+#[cfg(feature = "trace")]
+mod chrome;
+mod error;
+#[cfg(feature = "trace")]
+mod graph;
 mod node;
+mod order;
+mod queue;
 mod slot;
+mod subscription;
+mod sync;
 mod trace;
 
 // ---
@@ -88,20 +153,259 @@ fn unborrow_mut(value: &Cell<BorrowFlag>) {
     value.set(value.get() + 1);
 }
 
+#[inline(always)]
+fn borrow(value: &Cell<BorrowFlag>) {
+    value.set(value.get() + 1);
+}
+
+#[inline(always)]
+fn unborrow(value: &Cell<BorrowFlag>) {
+    value.set(value.get() - 1);
+}
+
 #[inline(always)]
 fn is_borrowed(value: &Cell<BorrowFlag>) -> bool {
     value.get() != 0
 }
 
+#[inline(always)]
+fn is_borrowed_mut(value: &Cell<BorrowFlag>) -> bool {
+    value.get() < 0
+}
+
+// Negative while mutably borrowed, positive counts the shared borrows, like `RefCell`.
 type BorrowFlag = isize;
 
+/// Entry of [STACK], describing a node that is currently emitted.
+#[derive(Clone, Copy)]
+struct Frame {
+    flag: *const Cell<BorrowFlag>,
+    item: *mut (),
+    id: TypeId,
+    name: Option<&'static str>,
+    type_name: &'static str,
+    // Emitted by `Node::emit_ref`, such frames can not be suspended.
+    shared: bool,
+}
+
+impl Frame {
+    fn label(&self) -> &'static str {
+        self.name.unwrap_or(self.type_name)
+    }
+}
+
+/// Identity of a type that, unlike [TypeId::of], does not require `T: 'static`.
+///
+/// Lifetimes are erased, which is fine for telling apart objects that share an address, since
+/// types differing only in lifetimes are the same object.
+fn type_id<T: ?Sized>() -> TypeId {
+    trait NonStaticAny {
+        fn type_id(&self) -> TypeId
+        where
+            Self: 'static;
+    }
+
+    impl<T: ?Sized> NonStaticAny for PhantomData<T> {
+        fn type_id(&self) -> TypeId
+        where
+            Self: 'static,
+        {
+            TypeId::of::<T>()
+        }
+    }
+
+    let phantom = PhantomData::<T>;
+    // unsafe: Only the lifetime of the trait object is extended, and `type_id` never touches any
+    // data of `T`, it only inspects the type.
+    NonStaticAny::type_id(unsafe {
+        mem::transmute::<&dyn NonStaticAny, &(dyn NonStaticAny + 'static)>(&phantom)
+    })
+}
+
+/// Pops the last [STACK] entry and releases the borrow of a node when dropped.
+///
+/// Being a guard ensures the state is restored even if the emitted handler panics.
+struct EmitGuard<'a> {
+    flag: &'a Cell<BorrowFlag>,
+    stack: *mut Vec<Frame>,
+    shared: bool,
+}
+
+impl<'a> EmitGuard<'a> {
+    #[inline(always)]
+    fn new(flag: &'a Cell<BorrowFlag>, stack: *mut Vec<Frame>, shared: bool) -> Self {
+        Self {
+            flag,
+            stack,
+            shared,
+        }
+    }
+}
+
+impl Drop for EmitGuard<'_> {
+    #[inline(always)]
+    fn drop(&mut self) {
+        // unsafe: `stack` points to the thread-local `STACK` of this thread, which outlives
+        // every node emission. It is never borrowed for longer than immediate mutation.
+        unsafe { &mut *self.stack }.pop();
+        if self.shared {
+            unborrow(self.flag);
+        } else {
+            unborrow_mut(self.flag);
+        }
+    }
+}
+
+/// Reacquires the mutable borrow of a suspended node when dropped.
+///
+/// Being a guard ensures the node is borrowed again before its emit returns, even if the
+/// runner of [Suspend::suspend] panics.
+struct SuspendGuard {
+    flag: *const Cell<BorrowFlag>,
+}
+
+impl Drop for SuspendGuard {
+    #[inline(always)]
+    fn drop(&mut self) {
+        // unsafe: See `Suspend::suspend`, the flag outlives the suspension.
+        borrow_mut(unsafe { &*self.flag });
+    }
+}
+
 // ---
 
 thread_local! {
+    // Maximum length of `STACK`, `None` if unlimited.
+    static MAX_DEPTH: Cell<Option<usize>> = const { Cell::new(None) };
+
     // `STACK` is parallel to the callstack. The last element represents the current active item
     // being invoked on a `Node`. It is inside an `UnsafeCell` because it is only ever
     // pushed/popped in the same function, and we can prove that borrows are not propagated.
-    static STACK: UnsafeCell<Vec<(*const Cell<BorrowFlag>, *mut (), usize)>> = UnsafeCell::new(Vec::new());
+    static STACK: UnsafeCell<Vec<Frame>> = const { UnsafeCell::new(Vec::new()) };
+}
+
+/// Set the maximum number of nested node emissions on the current thread.
+///
+/// Emitting a node beyond this depth fails with [Error::DepthLimit] instead of eventually
+/// overflowing the native stack, which happens when signals accidentally form a cycle. Passing
+/// `None`, the default, removes the limit.
+///
+/// ```
+/// use revent::{Error, Node, Suspend};
+///
+/// revent::set_max_depth(Some(1));
+///
+/// let node = Node::new(());
+/// node.emit(|x| {
+///     x.suspend(|| {
+///         assert!(matches!(node.try_emit(|_| {}), Err(Error::DepthLimit(_))));
+///     });
+/// });
+/// ```
+pub fn set_max_depth(depth: Option<usize>) {
+    MAX_DEPTH.with(|x| x.set(depth));
+}
+
+/// Get the maximum number of nested node emissions on the current thread.
+///
+/// See [set_max_depth].
+pub fn max_depth() -> Option<usize> {
+    MAX_DEPTH.with(|x| x.get())
+}
+
+/// Description of a node that is currently being emitted, as returned by [current_stack].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct StackFrame {
+    /// Address identifying the node, shared by all clones of it.
+    pub id: usize,
+    /// Name given to the node by [Node::named], if any.
+    pub name: Option<&'static str>,
+    /// Name of the type held by the node.
+    pub type_name: &'static str,
+    /// Whether the node has [suspend](Suspend::suspend)ed itself, allowing it to be emitted
+    /// again.
+    pub suspended: bool,
+    /// Whether the node was emitted by [Node::emit_ref], which only grants shared access.
+    pub shared: bool,
+}
+
+impl std::fmt::Display for StackFrame {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.name {
+            Some(name) => write!(f, "{} ({})", name, self.type_name)?,
+            None => write!(f, "{}", self.type_name)?,
+        }
+        write!(f, " at {:#x}", self.id)?;
+        if self.shared {
+            write!(f, ", shared")?;
+        }
+        if self.suspended {
+            write!(f, ", suspended")?;
+        }
+        Ok(())
+    }
+}
+
+/// List the nodes currently being emitted on this thread, outermost first.
+///
+/// ```
+/// use revent::{Node, Suspend};
+///
+/// assert!(revent::current_stack().is_empty());
+///
+/// let outer = Node::named("outer", ());
+/// let inner = Node::named("inner", ());
+///
+/// outer.emit(|x| {
+///     x.suspend(|| {
+///         inner.emit(|_| {
+///             let stack = revent::current_stack();
+///             assert_eq!(stack[0].name, Some("outer"));
+///             assert!(stack[0].suspended);
+///             assert_eq!(stack[1].name, Some("inner"));
+///             assert!(!stack[1].suspended);
+///         });
+///     });
+/// });
+/// ```
+pub fn current_stack() -> Vec<StackFrame> {
+    STACK.with(|x| {
+        // unsafe: `STACK` is only borrowed for immediate inspection.
+        let stack = unsafe { &*x.get() };
+        stack
+            .iter()
+            .enumerate()
+            .map(|(index, frame)| {
+                // Only the innermost frame of a node can be mutably borrowed, emitting it again
+                // requires all outer frames to be suspended. Shared frames are never suspended.
+                let innermost = stack[index + 1..].iter().all(|x| x.flag != frame.flag);
+                // unsafe: The flag outlives the frame, see `Suspend::try_suspend`.
+                let borrowed = is_borrowed_mut(unsafe { &*frame.flag });
+                StackFrame {
+                    id: frame.flag as usize,
+                    name: frame.name,
+                    type_name: frame.type_name,
+                    suspended: !frame.shared && (!innermost || !borrowed),
+                    shared: frame.shared,
+                }
+            })
+            .collect()
+    })
+}
+
+// Appended to panic messages of borrow conflicts.
+fn stack_dump(error: &Error) -> String {
+    if !matches!(
+        error,
+        Error::AlreadyBorrowed(_) | Error::HeldByOtherThread(_)
+    ) {
+        return String::new();
+    }
+    let mut dump = String::from("\nstack, outermost first:");
+    for (index, frame) in current_stack().iter().enumerate() {
+        dump.push_str(&format!("\n    {}: {}", index, frame));
+    }
+    dump
 }
 
 // ---
@@ -130,35 +434,67 @@ pub trait Suspend {
     /// });
     /// ```
     fn suspend<F: FnOnce() -> R, R>(&mut self, runner: F) -> R
+    where
+        Self: Sized,
+    {
+        match self.try_suspend(runner) {
+            Ok(data) => data,
+            Err(error) => panic!("revent: suspend: {}", error),
+        }
+    }
+
+    /// Suspend this object and run `runner`, returning an [Error] instead of panicking.
+    ///
+    /// `runner` is only invoked if the object can be suspended.
+    ///
+    /// ```
+    /// use revent::{Error, Node, Suspend};
+    /// let node1 = Node::new(());
+    /// let node2 = Node::named("node2", ());
+    /// node1.emit(|x1| {
+    ///     node2.emit(|_| {
+    ///         assert_eq!(
+    ///             x1.try_suspend(|| {}),
+    ///             Err(Error::NotTopOfStack {
+    ///                 expected: "node2",
+    ///                 actual: "()"
+    ///             })
+    ///         );
+    ///     });
+    /// });
+    /// ```
+    fn try_suspend<F: FnOnce() -> R, R>(&mut self, runner: F) -> Result<R, Error>
     where
         Self: Sized,
     {
         let last = STACK.with(|x| {
             // unsafe: We know there exist no other borrows of `STACK`. It is _never_ borrowed
             // for more than immediate mutation or acquiring information.
-            if let Some(last) = unsafe { &mut *x.get() }.last() {
-                *last
-            } else {
-                panic!("revent: suspend: not inside node context");
-            }
+            unsafe { &*x.get() }.last().copied()
         });
+        let last = last.ok_or(Error::NotInNodeContext)?;
 
         let item: *mut _ = self;
-        if last.1 != item as *mut () || last.2 != mem::size_of::<Self>() {
-            panic!("revent: suspend: item not expected",);
+        // Comparing the type as well as the address rejects fields sharing the address of the
+        // emitted object, such as the first field of a struct. Shared emissions hand out no
+        // `&mut`, so they are never suspended.
+        if last.shared || last.item != item as *mut () || last.id != type_id::<Self>() {
+            return Err(Error::NotTopOfStack {
+                expected: last.label(),
+                actual: any::type_name::<Self>(),
+            });
         }
 
-        // unsafe: The pointer `last.0` to `*const Cell<BorrowFlag>` is valid because it refers to a
-        // variable on the stack from at least 2 stack frames earlier. The pointer comes from
-        // `Node` which guarantees that the pointee exists.
+        // unsafe: The pointer `last.flag` to `*const Cell<BorrowFlag>` is valid because it
+        // refers to a variable on the stack from at least 2 stack frames earlier. The pointer
+        // comes from `Node` which guarantees that the pointee exists.
         //
         // We do _not_ need to check the value of the borrow flag since we got `&mut`, so we know
         // it is guaranteed a mutable borrow.
-        unborrow_mut(unsafe { &*last.0 });
-        let data = (runner)();
-        // unsafe: See above.
-        borrow_mut(unsafe { &*last.0 });
-        data
+        unborrow_mut(unsafe { &*last.flag });
+        let _trace = Trace::suspend(last.flag as usize, last.name, last.type_name);
+        let _guard = SuspendGuard { flag: last.flag };
+        Ok((runner)())
     }
 }
 
@@ -169,6 +505,7 @@ impl<T> Suspend for T {}
 #[cfg(test)]
 mod tests {
     use crate::*;
+    use std::panic::{self, AssertUnwindSafe};
 
     #[test]
     #[should_panic(expected = "revent: suspend: not inside node context")]
@@ -186,10 +523,10 @@ mod tests {
     }
 
     #[test]
-    #[should_panic(expected = "revent: suspend: item not expected")]
+    #[should_panic(expected = "revent: suspend: item not expected: () (top of stack is y)")]
     fn suspend_not_top() {
         let x = Node::new(());
-        let y = Node::new(());
+        let y = Node::named("y", ());
         x.emit(|x1| {
             y.emit(|_| {
                 x1.suspend(|| {});
@@ -211,7 +548,7 @@ mod tests {
             fn function(&mut self, hub: &Channel<dyn Trait>);
         }
 
-        let mut channel = Channel::<dyn Trait>::new();
+        let channel = Channel::<dyn Trait>::new();
 
         struct My {
             value: usize,
@@ -233,7 +570,7 @@ mod tests {
             }
         }
 
-        channel.insert(0, Node::new(My { value: 12 }));
+        channel.insert(0, node_dyn!(Node::new(My { value: 12 }), dyn Trait));
 
         channel.emit(|x| {
             x.function(&channel);
@@ -255,6 +592,144 @@ mod tests {
         });
     }
 
+    fn stack_len() -> usize {
+        STACK.with(|x| unsafe { &*x.get() }.len())
+    }
+
+    #[test]
+    fn panic_in_emit_restores_state() {
+        let node = Node::new(0);
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            node.emit(|_| {
+                panic!("handler failure");
+            });
+        }));
+
+        assert!(result.is_err());
+        assert_eq!(stack_len(), 0);
+        node.emit(|x| {
+            *x += 1;
+        });
+    }
+
+    #[test]
+    fn conflict_dumps_stack() {
+        let outer = Node::named("outer", 0u8);
+        let inner = Node::named("inner", 0u16);
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            outer.emit(|x| {
+                x.suspend(|| {
+                    inner.emit(|_| inner.emit(|_| {}));
+                });
+            });
+        }));
+
+        let message = result.unwrap_err().downcast::<String>().unwrap();
+        let lines = message.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines[0],
+            "revent: emit: accessing already borrowed item: outer -> inner -> inner"
+        );
+        assert_eq!(lines[1], "stack, outermost first:");
+        assert!(lines[2].starts_with("    0: outer (u8) at 0x"));
+        assert!(lines[2].ends_with(", suspended"));
+        assert!(lines[3].starts_with("    1: inner (u16) at 0x"));
+        assert!(!lines[3].ends_with(", suspended"));
+        assert_eq!(lines.len(), 4);
+        assert!(current_stack().is_empty());
+    }
+
+    #[test]
+    fn panic_in_suspend_restores_state() {
+        let node = Node::new(0);
+
+        node.emit(|x| {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                x.suspend(|| {
+                    node.emit(|_| {
+                        panic!("handler failure");
+                    });
+                });
+            }));
+            assert!(result.is_err());
+            assert_eq!(stack_len(), 1);
+
+            // The node must be borrowed again after unwinding out of `suspend`.
+            x.suspend(|| {
+                node.emit(|_| {});
+            });
+        });
+
+        assert_eq!(stack_len(), 0);
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn panic_in_nested_chain_restores_state(depth: u8) {
+        let depth = depth % 32;
+        let nodes = (0..=depth).map(Node::new).collect::<Vec<_>>();
+
+        fn call(nodes: &[Node<u8>], index: usize) {
+            if index == nodes.len() {
+                panic!("handler failure");
+            }
+            nodes[index].emit(|x| {
+                x.suspend(|| {
+                    nodes[index].emit(|_| {
+                        call(nodes, index + 1);
+                    });
+                });
+            });
+        }
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| call(&nodes, 0)));
+
+        assert!(result.is_err());
+        assert_eq!(stack_len(), 0);
+        for node in &nodes {
+            node.emit(|x| {
+                x.suspend(|| {});
+            });
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "revent: suspend: item not expected")]
+    fn suspend_same_size_field_check() {
+        struct Wrapper {
+            a: u64,
+        }
+
+        let my_node = Node::new(Wrapper { a: 0 });
+
+        my_node.emit(|x| {
+            x.a.suspend(|| {});
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "revent: suspend: item not expected")]
+    fn suspend_newtype_field_check() {
+        struct Newtype(Vec<u8>);
+
+        let my_node = Node::new(Newtype(vec![]));
+
+        my_node.emit(|x| {
+            x.0.suspend(|| {});
+        });
+    }
+
+    #[test]
+    fn suspend_non_static() {
+        let value = 123;
+        let my_node = Node::new(&value);
+
+        my_node.emit(|x| {
+            x.suspend(|| {});
+        });
+    }
+
     #[test]
     #[should_panic(expected = "revent: emit: accessing already borrowed item")]
     fn node_inside_node() {
@@ -270,3 +745,92 @@ mod tests {
         });
     }
 }
+
+#[cfg(all(test, feature = "derive"))]
+mod derive_tests {
+    use crate::*;
+
+    #[signal]
+    trait Count {
+        fn count(&mut self, amount: usize);
+        fn total(&self) -> usize;
+    }
+
+    impl Count for usize {
+        fn count(&mut self, amount: usize) {
+            *self += amount;
+        }
+
+        fn total(&self) -> usize {
+            *self
+        }
+    }
+
+    #[derive(Hub)]
+    struct Counters {
+        all: Channel<dyn Count>,
+        one: Slot<dyn Count>,
+    }
+
+    #[test]
+    fn generated_helpers() {
+        let mut hub = Counters::default();
+
+        let node = Node::new(0);
+        hub.all.insert(0, node_dyn!(node.clone(), dyn Count));
+        hub.all.insert(0, Upcast::upcast(Node::new(10)));
+        hub.one.insert(node_dyn!(node, dyn Count));
+
+        hub.all.count(1);
+        hub.one.count(5);
+
+        assert_eq!(hub.all.total(), [6, 11]);
+        assert_eq!(hub.one.total(), 6);
+    }
+
+    #[test]
+    fn shared_helpers_borrow_shared() {
+        let mut hub = Counters::default();
+
+        let node = Node::new(3);
+        hub.all.insert(0, node_dyn!(node.clone(), dyn Count));
+        hub.one.insert(node_dyn!(node.clone(), dyn Count));
+
+        node.emit_ref(|x| {
+            assert_eq!(hub.all.total(), [*x]);
+            assert_eq!(hub.one.total(), *x);
+        });
+    }
+
+    #[derive(Subscriber)]
+    #[subscribe(Counters: all, one)]
+    struct Counter(usize);
+
+    impl Count for Counter {
+        fn count(&mut self, amount: usize) {
+            self.0 += amount;
+        }
+
+        fn total(&self) -> usize {
+            self.0
+        }
+    }
+
+    #[test]
+    fn subscribe_to_hub() {
+        let mut hub = Counters::default();
+
+        let node = Node::new(Counter(0));
+        let subscriptions = hub.subscribe(&node);
+        assert_eq!(subscriptions.len(), 2);
+
+        hub.all.count(1);
+        hub.one.count(2);
+        assert_eq!(node.emit(|x| x.0), 3);
+
+        drop(subscriptions);
+        hub.all.count(1);
+        assert_eq!(hub.all.total(), []);
+        assert!(hub.one.try_emit(|x| x.total()).is_err());
+    }
+}
@@ -1,11 +1,15 @@
-use crate::{borrow_mut, is_borrowed, unborrow_mut, BorrowFlag, Trace, STACK};
+use crate::{
+    borrow, borrow_mut, is_borrowed, is_borrowed_mut, max_depth, stack_dump, type_id, BorrowFlag,
+    EmitGuard, Error, Frame, Trace, TraceKind, STACK,
+};
 use std::{
+    any::{self, TypeId},
     cell::{Cell, UnsafeCell},
-    marker::Unsize,
-    mem,
-    ops::CoerceUnsized,
-    rc::Rc,
+    fmt, ptr,
+    rc::{Rc, Weak},
 };
+#[cfg(feature = "nightly")]
+use std::{marker::Unsize, ops::CoerceUnsized};
 
 /// Node containing arbitrary data.
 ///
@@ -14,14 +18,25 @@ use std::{
 ///
 /// Node is fundamentally the same as [RefCell](std::cell::RefCell), but does one more thing:
 /// it allows suspension of the last emitted node by using its `&mut`. Suspending allows the
-/// node to be reborrowed without aliasing.
+/// node to be reborrowed without aliasing. Like `RefCell::borrow`, [emit_ref](Node::emit_ref)
+/// grants shared access to any number of handlers at once.
 pub struct Node<T: ?Sized> {
-    item: Rc<(Cell<BorrowFlag>, UnsafeCell<T>)>,
-    size: usize,
+    item: Rc<Inner<T>>,
+    // Type of the object stored in this node at creation, before any unsizing coercion.
+    id: TypeId,
+    type_name: &'static str,
     trace: Trace,
-    stack: *mut Vec<(*const Cell<BorrowFlag>, *mut (), usize)>,
+    stack: *mut Vec<Frame>,
+}
+
+struct Inner<T: ?Sized> {
+    flag: Cell<BorrowFlag>,
+    limit: Cell<Option<usize>>,
+    name: Option<&'static str>,
+    data: UnsafeCell<T>,
 }
 
+#[cfg(feature = "nightly")]
 impl<T, U> CoerceUnsized<Node<U>> for Node<T>
 where
     T: Unsize<U> + ?Sized,
@@ -29,11 +44,181 @@ where
 {
 }
 
-impl<T> Clone for Node<T> {
+/// Non-owning reference to the contents of a [Node].
+///
+/// Created by [Node::downgrade]. Does not keep the contents of the node alive, so containers
+/// holding a `WeakNode` skip it once all strong [Node]s are dropped.
+///
+/// ```
+/// use revent::{Channel, Node};
+///
+/// let channel = Channel::new();
+///
+/// let node = Node::new(123);
+/// channel.insert_weak(0, Node::downgrade(&node));
+///
+/// let mut count = 0;
+/// channel.emit(|_| count += 1);
+/// assert_eq!(count, 1);
+///
+/// drop(node);
+///
+/// channel.emit(|_| count += 1);
+/// assert_eq!(count, 1);
+/// ```
+pub struct WeakNode<T: ?Sized> {
+    item: Weak<Inner<T>>,
+    id: TypeId,
+    type_name: &'static str,
+    trace: Trace,
+    stack: *mut Vec<Frame>,
+}
+
+#[cfg(feature = "nightly")]
+impl<T, U> CoerceUnsized<WeakNode<U>> for WeakNode<T>
+where
+    T: Unsize<U> + ?Sized,
+    U: ?Sized,
+{
+}
+
+impl<T: ?Sized> Clone for WeakNode<T> {
+    fn clone(&self) -> Self {
+        Self {
+            item: self.item.clone(),
+            id: self.id,
+            type_name: self.type_name,
+            trace: self.trace.clone(),
+            stack: self.stack,
+        }
+    }
+}
+
+impl<T: ?Sized> WeakNode<T> {
+    /// Attempt to acquire a strong [Node], returns `None` if all strong nodes have been dropped.
+    pub fn upgrade(&self) -> Option<Node<T>> {
+        Some(Node {
+            item: self.item.upgrade()?,
+            id: self.id,
+            type_name: self.type_name,
+            trace: self.trace.clone(),
+            stack: self.stack,
+        })
+    }
+
+    /// Returns true if this points to the same allocation as `node`.
+    #[inline]
+    pub fn ptr_eq(&self, node: &Node<T>) -> bool {
+        self.item.as_ptr() as *const () == Rc::as_ptr(&node.item) as *const ()
+    }
+}
+
+/// Convert a `Node<A>` into a `Node<dyn Trait>` on stable Rust.
+///
+/// Takes a node and the unsized type to convert it into. Compilation fails unless the contents of
+/// the node can be unsized into that type. With the `nightly` feature this conversion also
+/// happens implicitly.
+///
+/// ```
+/// use revent::{node_dyn, Channel, Node};
+///
+/// trait Trait {
+///     fn function(&mut self);
+/// }
+///
+/// struct Object;
+/// impl Trait for Object {
+///     fn function(&mut self) {}
+/// }
+///
+/// let channel = Channel::<dyn Trait>::new();
+/// channel.insert(0, node_dyn!(Node::new(Object), dyn Trait));
+/// ```
+#[macro_export]
+macro_rules! node_dyn {
+    ($node:expr, $type:ty $(,)?) => {
+        match $node {
+            // unsafe: the closure is only an unsizing coercion of the data pointer.
+            node => unsafe { $crate::Node::__coerce(node, |x| -> *const $type { x }) },
+        }
+    };
+}
+
+/// Conversion of a `Node<S>` into a `Node<Self>`.
+///
+/// Lets generic code such as [Subscriber](crate::Subscriber) implementations insert a node into
+/// containers of trait objects. `#[signal]` implements this trait for `dyn Trait`, otherwise it
+/// is usually implemented using [node_dyn].
+pub trait Upcast<S> {
+    /// Convert `node` into a node of this type.
+    fn upcast(node: Node<S>) -> Node<Self>;
+}
+
+/// Strong or weak reference to a node, as held by containers.
+pub(crate) enum Link<T: ?Sized> {
+    Strong(Node<T>),
+    Weak(WeakNode<T>),
+}
+
+impl<T: ?Sized> Link<T> {
+    /// Emit on the referenced node, returns `None` if the node has been dropped.
+    #[inline]
+    pub(crate) fn try_emit<F: FnOnce(&mut T) -> R, R>(
+        &self,
+        handler: F,
+    ) -> Option<Result<R, Error>> {
+        match self {
+            Link::Strong(node) => Some(node.try_emit(handler)),
+            Link::Weak(node) => node.upgrade().map(|node| node.try_emit(handler)),
+        }
+    }
+
+    /// Emit a shared borrow on the referenced node, returns `None` if the node has been dropped.
+    #[inline]
+    pub(crate) fn try_emit_ref<F: FnOnce(&T) -> R, R>(
+        &self,
+        handler: F,
+    ) -> Option<Result<R, Error>> {
+        match self {
+            Link::Strong(node) => Some(node.try_emit_ref(handler)),
+            Link::Weak(node) => node.upgrade().map(|node| node.try_emit_ref(handler)),
+        }
+    }
+
+    /// Acquire a strong node, returns `None` if the node has been dropped.
+    pub(crate) fn upgrade(&self) -> Option<Node<T>> {
+        match self {
+            Link::Strong(node) => Some(node.clone()),
+            Link::Weak(node) => node.upgrade(),
+        }
+    }
+
+    /// Name and label of the referenced node, `None` if the node has been dropped.
+    pub(crate) fn names(&self) -> Option<(Option<&'static str>, &'static str)> {
+        self.upgrade().map(|node| (node.name(), node.label()))
+    }
+
+    pub(crate) fn is_alive(&self) -> bool {
+        match self {
+            Link::Strong(_) => true,
+            Link::Weak(node) => node.item.strong_count() > 0,
+        }
+    }
+
+    pub(crate) fn ptr_eq(&self, node: &Node<T>) -> bool {
+        match self {
+            Link::Strong(this) => Node::ptr_eq(this, node),
+            Link::Weak(this) => this.ptr_eq(node),
+        }
+    }
+}
+
+impl<T: ?Sized> Clone for Node<T> {
     fn clone(&self) -> Self {
         Self {
             item: self.item.clone(),
-            size: self.size,
+            id: self.id,
+            type_name: self.type_name,
             trace: self.trace.clone(),
             stack: STACK.with(|x| x.get()),
         }
@@ -43,12 +228,29 @@ impl<T> Clone for Node<T> {
 impl<T> Node<T> {
     /// Create a new node.
     pub fn new(item: T) -> Self {
-        Self {
-            item: Rc::new((Cell::new(0), UnsafeCell::new(item))),
-            size: mem::size_of::<T>(),
-            trace: Trace::empty(),
-            stack: STACK.with(|x| x.get()),
-        }
+        Self::new_with_trace_object(item, None, Trace::empty())
+    }
+
+    /// Create a new node with a name.
+    ///
+    /// The name is used instead of the type name in errors, panic messages, traces and
+    /// [Debug](std::fmt::Debug) output. It is shared by all clones of this node.
+    ///
+    /// ```
+    /// use revent::{Error, Node};
+    ///
+    /// let player = Node::named("player", 100);
+    /// assert_eq!(player.name(), Some("player"));
+    ///
+    /// player.emit(|_| {
+    ///     assert_eq!(
+    ///         player.try_emit(|_| {}),
+    ///         Err(Error::AlreadyBorrowed(vec!["player", "player"]))
+    ///     );
+    /// });
+    /// ```
+    pub fn named(name: &'static str, item: T) -> Self {
+        Self::new_with_trace_object(item, Some(name), Trace::empty())
     }
 
     /// Create a new node with a tracing function.
@@ -58,10 +260,20 @@ impl<T> Node<T> {
     ///
     /// Requires the `trace` feature to be enabled to actually use the `trace` function.
     pub fn new_with_trace(item: T, trace: impl Fn(usize) + 'static) -> Self {
+        Self::new_with_trace_object(item, None, Trace::new(trace))
+    }
+
+    fn new_with_trace_object(item: T, name: Option<&'static str>, trace: Trace) -> Self {
         Self {
-            item: Rc::new((Cell::new(0), UnsafeCell::new(item))),
-            size: mem::size_of::<T>(),
-            trace: Trace::new(trace),
+            item: Rc::new(Inner {
+                flag: Cell::new(0),
+                limit: Cell::new(None),
+                name,
+                data: UnsafeCell::new(item),
+            }),
+            id: type_id::<T>(),
+            type_name: any::type_name::<T>(),
+            trace,
             stack: STACK.with(|x| x.get()),
         }
     }
@@ -105,25 +317,200 @@ impl<T: ?Sized> Node<T> {
     ///     });
     /// });
     /// ```
+    ///
+    /// If `handler` panics, the node is released before unwinding continues, so it can be
+    /// emitted again once the panic has been caught.
     pub fn emit<F: FnOnce(&mut T) -> R, R>(&self, handler: F) -> R {
+        match self.try_emit(handler) {
+            Ok(data) => data,
+            Err(error) => panic!("revent: emit: {}{}", error, stack_dump(&error)),
+        }
+    }
+
+    /// Acquire a `&mut` to the contents of the node, returning an [Error] instead of panicking.
+    ///
+    /// `handler` is only invoked if the node could be borrowed.
+    ///
+    /// ```
+    /// use revent::{Error, Node};
+    /// let node = Node::new(123);
+    ///
+    /// node.emit(|_| {
+    ///     assert_eq!(
+    ///         node.try_emit(|_| {}),
+    ///         Err(Error::AlreadyBorrowed(vec!["i32", "i32"]))
+    ///     );
+    /// });
+    /// ```
+    pub fn try_emit<F: FnOnce(&mut T) -> R, R>(&self, handler: F) -> Result<R, Error> {
+        // unsafe: `item` is an `Rc`, which guarantees the existence and validity of the
+        // pointee. It is also safeguarded by the borrow flag, which `access` has proven to be
+        // unborrowed, otherwise we would have returned an error.
+        self.access(false, |data| (handler)(unsafe { &mut *data }))
+    }
+
+    /// Acquire a `&` to the contents of the node.
+    ///
+    /// Any number of shared emissions of the same node may be nested, but the node can not be
+    /// [emit](Node::emit)ted mutably until all of them have returned. The contents can not
+    /// [Suspend](crate::Suspend) themselves, as there is no `&mut` to suspend.
+    ///
+    /// ```
+    /// use revent::Node;
+    ///
+    /// let node = Node::new(123);
+    ///
+    /// let sum = node.emit_ref(|x| node.emit_ref(|y| *x + *y));
+    /// assert_eq!(sum, 246);
+    /// ```
+    ///
+    /// # Panics #
+    ///
+    /// Panics if the node is mutably borrowed without being suspended.
+    ///
+    /// ```should_panic
+    /// use revent::Node;
+    /// let node = Node::new(123);
+    ///
+    /// node.emit(|_| {
+    ///     node.emit_ref(|_| {});
+    /// });
+    /// ```
+    pub fn emit_ref<F: FnOnce(&T) -> R, R>(&self, handler: F) -> R {
+        match self.try_emit_ref(handler) {
+            Ok(data) => data,
+            Err(error) => panic!("revent: emit: {}{}", error, stack_dump(&error)),
+        }
+    }
+
+    /// Acquire a `&` to the contents of the node, returning an [Error] instead of panicking.
+    ///
+    /// `handler` is only invoked if the node could be borrowed.
+    ///
+    /// ```
+    /// use revent::{Error, Node};
+    /// let node = Node::new(123);
+    ///
+    /// node.emit_ref(|_| {
+    ///     assert_eq!(node.try_emit_ref(|x| *x), Ok(123));
+    ///     assert_eq!(
+    ///         node.try_emit(|_| {}),
+    ///         Err(Error::AlreadyBorrowed(vec!["i32", "i32"]))
+    ///     );
+    /// });
+    /// ```
+    pub fn try_emit_ref<F: FnOnce(&T) -> R, R>(&self, handler: F) -> Result<R, Error> {
+        // unsafe: As in `try_emit`, except that only other shared borrows may exist.
+        self.access(true, |data| (handler)(unsafe { &*data }))
+    }
+
+    // Borrows the node, shared or mutably, and runs `handler` with its frame pushed.
+    #[inline]
+    fn access<F: FnOnce(*mut T) -> R, R>(&self, shared: bool, handler: F) -> Result<R, Error> {
         self.trace.log();
 
-        if is_borrowed(self.flag()) {
-            panic!("revent: emit: accessing already borrowed item");
+        let conflict = if shared {
+            is_borrowed_mut(self.flag())
+        } else {
+            is_borrowed(self.flag())
+        };
+        if conflict {
+            // unsafe: `STACK` is only borrowed for immediate inspection.
+            let stack = unsafe { &*self.stack };
+            return Err(Error::AlreadyBorrowed(self.chain(stack)));
         }
-        borrow_mut(self.flag());
+        self.check_limits()?;
+        if shared {
+            borrow(self.flag());
+        } else {
+            borrow_mut(self.flag());
+        }
+        // Declared before the guard and therefore dropped after it, so the exit is recorded once
+        // the frame is popped, at the same stack depth as the enter.
+        let flag: *const _ = self.flag();
+        let _trace = Trace::span(TraceKind::Node, flag as usize, self.name(), self.type_name);
 
-        unsafe { &mut *self.stack }.push((self.flag(), self.data().get() as *mut _, self.size));
+        unsafe { &mut *self.stack }.push(Frame {
+            flag: self.flag(),
+            item: self.data().get() as *mut _,
+            id: self.id,
+            name: self.name(),
+            type_name: self.type_name,
+            shared,
+        });
 
-        // unsafe: `item` is an `Rc`, which guarantees the existence and validity of the
-        // pointee. It is also safeguarded by `self.used`, which we have proven above to be
-        // `false`, otherwise we would have panicked.
-        let object = unsafe { &mut *self.data().get() };
-        let data = (handler)(object);
+        // Pops the stack and releases the borrow when leaving this scope, including when
+        // `handler` panics.
+        let _guard = EmitGuard::new(self.flag(), self.stack, shared);
 
-        unsafe { &mut *self.stack }.pop();
-        unborrow_mut(self.flag());
-        data
+        Ok((handler)(self.data().get()))
+    }
+
+    /// Limit how many times this node can be emitted while already being emitted further up the
+    /// stack, which is possible by [suspend](crate::Suspend::suspend)ing it.
+    ///
+    /// A limit of `Some(1)` forbids reentrancy altogether. Exceeding the limit fails with
+    /// [Error::ReentrancyLimit]. The limit is shared between all clones of this node. `None`, the
+    /// default, removes the limit.
+    ///
+    /// ```
+    /// use revent::{Error, Node, Suspend};
+    ///
+    /// let node = Node::new(());
+    /// node.set_reentrancy_limit(Some(1));
+    ///
+    /// node.emit(|x| {
+    ///     x.suspend(|| {
+    ///         assert!(matches!(node.try_emit(|_| {}), Err(Error::ReentrancyLimit(_))));
+    ///     });
+    /// });
+    /// ```
+    pub fn set_reentrancy_limit(&self, limit: Option<usize>) {
+        self.item.limit.set(limit);
+    }
+
+    fn check_limits(&self) -> Result<(), Error> {
+        // unsafe: `STACK` is only borrowed for immediate inspection.
+        let stack = unsafe { &*self.stack };
+
+        if let Some(depth) = max_depth() {
+            if stack.len() >= depth {
+                return Err(Error::DepthLimit(self.chain(stack)));
+            }
+        }
+
+        if let Some(limit) = self.item.limit.get() {
+            let flag: *const _ = self.flag();
+            let mut active = stack.iter().enumerate().filter(|(_, x)| x.flag == flag);
+            if let Some((first, _)) = active.next() {
+                if 1 + active.count() >= limit {
+                    return Err(Error::ReentrancyLimit(self.chain(&stack[first..])));
+                }
+            } else if limit == 0 {
+                return Err(Error::ReentrancyLimit(self.chain(&[])));
+            }
+        }
+
+        Ok(())
+    }
+
+    // Labels of `frames` followed by the label of this node.
+    fn chain(&self, frames: &[Frame]) -> Vec<&'static str> {
+        frames
+            .iter()
+            .map(Frame::label)
+            .chain(Some(self.label()))
+            .collect()
+    }
+
+    /// Name given to this node by [Node::named], if any.
+    pub fn name(&self) -> Option<&'static str> {
+        self.item.name
+    }
+
+    /// Name of this node, or the type name of its contents if it is unnamed.
+    pub fn label(&self) -> &'static str {
+        self.name().unwrap_or(self.type_name)
     }
 
     /// Returns true if two `Node`s point to the same allocation.
@@ -132,14 +519,67 @@ impl<T: ?Sized> Node<T> {
         Rc::ptr_eq(&this.item, &other.item)
     }
 
+    /// Create a [WeakNode] pointing to the same allocation.
+    pub fn downgrade(this: &Self) -> WeakNode<T> {
+        WeakNode {
+            item: Rc::downgrade(&this.item),
+            id: this.id,
+            type_name: this.type_name,
+            trace: this.trace.clone(),
+            stack: this.stack,
+        }
+    }
+
+    /// Implementation detail of [node_dyn].
+    ///
+    /// # Safety #
+    ///
+    /// `coerce` must return its argument unchanged, apart from unsizing it to `U`.
+    #[doc(hidden)]
+    pub unsafe fn __coerce<U: ?Sized>(this: Self, coerce: fn(*const T) -> *const U) -> Node<U> {
+        let Node {
+            item,
+            id,
+            type_name,
+            trace,
+            stack,
+        } = this;
+
+        // Unsize the pointer to the data, then move back to the start of the allocation while
+        // keeping the new metadata. This is the same layout an unsizing coercion of the `Rc`
+        // produces.
+        let inner = Rc::into_raw(item);
+        let data = UnsafeCell::raw_get(ptr::addr_of!((*inner).data)).cast_const();
+        let offset = data.cast::<u8>().offset_from(inner.cast::<u8>()) as usize;
+        let inner = coerce(data).byte_sub(offset) as *const Inner<U>;
+
+        Node {
+            item: Rc::from_raw(inner),
+            id,
+            type_name,
+            trace,
+            stack,
+        }
+    }
+
     #[inline]
     fn data(&self) -> &UnsafeCell<T> {
-        &self.item.1
+        &self.item.data
     }
 
     #[inline]
     fn flag(&self) -> &Cell<BorrowFlag> {
-        &self.item.0
+        &self.item.flag
+    }
+}
+
+impl<T: ?Sized> fmt::Debug for Node<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Node")
+            .field("name", &self.name())
+            .field("type_name", &self.type_name)
+            .field("borrowed", &is_borrowed(self.flag()))
+            .finish()
     }
 }
 
@@ -158,6 +598,189 @@ mod tests {
             assert_eq!(*x, 1);
         });
     }
+
+    #[test]
+    fn weak_upgrade() {
+        let node = Node::new(123);
+        let weak = Node::downgrade(&node);
+
+        assert!(weak.ptr_eq(&node));
+        weak.upgrade().unwrap().emit(|x| *x += 1);
+        node.emit(|x| assert_eq!(*x, 124));
+
+        drop(node);
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[test]
+    fn node_dyn_shares_allocation() {
+        #[repr(align(64))]
+        struct Aligned(u8);
+
+        trait Value {
+            fn value(&mut self) -> &mut u8;
+        }
+
+        impl Value for Aligned {
+            fn value(&mut self) -> &mut u8 {
+                &mut self.0
+            }
+        }
+
+        let node = Node::new(Aligned(1));
+        let object = node_dyn!(node.clone(), dyn Value);
+
+        object.emit(|x| *x.value() += 1);
+        node.emit(|x| {
+            assert_eq!(x.0, 2);
+            x.suspend(|| {
+                object.emit(|x| *x.value() += 1);
+            });
+        });
+        assert!(object.try_emit(|_| {}).is_ok());
+        node.emit(|x| assert_eq!(x.0, 3));
+    }
+
+    #[cfg(feature = "nightly")]
+    #[test]
+    fn implicit_coercion() {
+        let node: Node<dyn std::fmt::Debug> = Node::new(123);
+        node.emit(|x| assert_eq!(format!("{:?}", x), "123"));
+    }
+
+    #[test]
+    fn reentrancy_limit_reports_cycle() {
+        let outer = Node::new(0u8);
+        let inner = Node::new(0u16);
+        outer.set_reentrancy_limit(Some(2));
+
+        outer.emit(|x| {
+            x.suspend(|| {
+                inner.emit(|y| {
+                    y.suspend(|| {
+                        outer.emit(|x| {
+                            x.suspend(|| {
+                                assert_eq!(
+                                    outer.try_emit(|_| {}),
+                                    Err(Error::ReentrancyLimit(vec!["u8", "u16", "u8", "u8"]))
+                                );
+                            });
+                        });
+                    });
+                });
+            });
+        });
+    }
+
+    #[test]
+    fn depth_limit() {
+        set_max_depth(Some(2));
+
+        let first = Node::new(());
+        let second = Node::new(0u8);
+        let third = Node::new(0u16);
+        first.emit(|_| {
+            second.emit(|_| {
+                assert_eq!(
+                    third.try_emit(|_| {}),
+                    Err(Error::DepthLimit(vec!["()", "u8", "u16"]))
+                );
+            });
+        });
+
+        set_max_depth(None);
+    }
+
+    #[test]
+    #[should_panic(expected = "revent: emit: depth limit exceeded: revent::node::tests::")]
+    fn depth_limit_cycle() {
+        trait Trait {
+            fn function(&mut self, hub: &Channel<dyn Trait>);
+        }
+
+        struct Cycle;
+
+        impl Trait for Cycle {
+            fn function(&mut self, channel: &Channel<dyn Trait>) {
+                self.suspend(|| {
+                    channel.emit(|item| {
+                        item.function(channel);
+                    });
+                });
+            }
+        }
+
+        set_max_depth(Some(100));
+
+        let channel = Channel::<dyn Trait>::new();
+        channel.insert(0, node_dyn!(Node::new(Cycle), dyn Trait));
+        channel.emit(|x| {
+            x.function(&channel);
+        });
+    }
+
+    #[test]
+    fn try_emit_reports_conflict() {
+        let node = Node::named("node", 123);
+        let result = node.emit(|x| {
+            assert_eq!(
+                node.try_emit(|_| unreachable!()),
+                Err(Error::AlreadyBorrowed(vec!["node", "node"]))
+            );
+            x.suspend(|| node.try_emit(|y| *y + 1))
+        });
+        assert_eq!(result, Ok(124));
+    }
+
+    #[test]
+    fn shared_emits_exclude_mutable() {
+        let node = Node::named("node", 1);
+
+        node.emit_ref(|x| {
+            node.emit_ref(|y| {
+                assert_eq!(*x + *y, 2);
+                assert_eq!(
+                    node.try_emit(|_| {}),
+                    Err(Error::AlreadyBorrowed(vec!["node", "node", "node"]))
+                );
+                let stack = current_stack();
+                assert!(stack.iter().all(|x| x.shared && !x.suspended));
+            });
+        });
+
+        node.emit(|x| {
+            assert_eq!(
+                node.try_emit_ref(|_| {}),
+                Err(Error::AlreadyBorrowed(vec!["node", "node"]))
+            );
+            x.suspend(|| {
+                node.emit_ref(|y| assert_eq!(*y, 1));
+                assert!(current_stack()[0].suspended);
+            });
+            *x += 1;
+        });
+
+        assert_eq!(node.emit_ref(|x| *x), 2);
+    }
+
+    #[test]
+    fn shared_emit_can_not_suspend() {
+        let outer = Node::new(0u8);
+        let inner = Node::new(0u16);
+
+        outer.emit(|x| {
+            inner.emit_ref(|_| {
+                assert_eq!(
+                    x.try_suspend(|| {}),
+                    Err(Error::NotTopOfStack {
+                        expected: "u16",
+                        actual: "u8"
+                    })
+                );
+            });
+            assert_eq!(x.try_suspend(|| {}), Ok(()));
+        });
+    }
 }
 
 #[cfg(all(test, feature = "trace"))]
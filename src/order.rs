@@ -0,0 +1,254 @@
+use crate::{Error, SubscriptionId};
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+};
+
+/// Ordering constraint of a channel entry relative to the nodes with a given name.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Constraint {
+    After(&'static str),
+    Before(&'static str),
+}
+
+/// Entry taking part in [sort].
+pub(crate) struct Listener {
+    pub(crate) id: SubscriptionId,
+    pub(crate) name: Option<&'static str>,
+    pub(crate) label: &'static str,
+    pub(crate) constraint: Option<Constraint>,
+}
+
+/// Edges implied by the constraints of a list of listeners, indexed by name.
+struct Graph<'a> {
+    listeners: &'a [Listener],
+    // Listeners carrying each name.
+    named: HashMap<&'static str, Vec<usize>>,
+    // Listeners constrained to come after each name.
+    after: HashMap<&'static str, Vec<usize>>,
+}
+
+impl<'a> Graph<'a> {
+    fn new(listeners: &'a [Listener]) -> Self {
+        let mut named = HashMap::<_, Vec<_>>::new();
+        let mut after = HashMap::<_, Vec<_>>::new();
+        for (index, listener) in listeners.iter().enumerate() {
+            if let Some(name) = listener.name {
+                named.entry(name).or_default().push(index);
+            }
+            if let Some(Constraint::After(name)) = listener.constraint {
+                after.entry(name).or_default().push(index);
+            }
+        }
+        Self {
+            listeners,
+            named,
+            after,
+        }
+    }
+
+    // Listeners that must be emitted after `index`.
+    fn successors(&self, index: usize) -> impl Iterator<Item = usize> + '_ {
+        let listener = &self.listeners[index];
+        let followers = listener.name.and_then(|name| self.after.get(name));
+        let before = match listener.constraint {
+            Some(Constraint::Before(name)) => self.named.get(name),
+            _ => None,
+        };
+        followers
+            .into_iter()
+            .chain(before)
+            .flatten()
+            .copied()
+            .filter(move |x| *x != index)
+    }
+}
+
+// Whether the constraints require `first` to be emitted before `second`.
+fn precedes(first: &Listener, second: &Listener) -> bool {
+    let after = match second.constraint {
+        Some(Constraint::After(name)) => first.name == Some(name),
+        _ => false,
+    };
+    let before = match first.constraint {
+        Some(Constraint::Before(name)) => second.name == Some(name),
+        _ => false,
+    };
+    after || before
+}
+
+/// Order `listeners` such that every constraint holds, otherwise keeping the order of their
+/// identifiers.
+///
+/// Constraints naming no listener are ignored. Fails with [Error::OrderingCycle] if the
+/// constraints contradict each other.
+pub(crate) fn sort(mut listeners: Vec<Listener>) -> Result<Vec<SubscriptionId>, Error> {
+    listeners.sort_by_key(|x| x.id);
+    let graph = Graph::new(&listeners);
+
+    let mut incoming = vec![0usize; listeners.len()];
+    for index in 0..listeners.len() {
+        for next in graph.successors(index) {
+            incoming[next] += 1;
+        }
+    }
+
+    // Kahn's algorithm, always emitting the ready listener with the lowest identifier.
+    let mut ready = (0..listeners.len())
+        .filter(|x| incoming[*x] == 0)
+        .map(Reverse)
+        .collect::<BinaryHeap<_>>();
+    let mut order = Vec::with_capacity(listeners.len());
+    while let Some(Reverse(index)) = ready.pop() {
+        order.push(listeners[index].id);
+        for next in graph.successors(index) {
+            incoming[next] -= 1;
+            if incoming[next] == 0 {
+                ready.push(Reverse(next));
+            }
+        }
+    }
+
+    if order.len() == listeners.len() {
+        Ok(order)
+    } else {
+        Err(Error::OrderingCycle(cycle(&graph, &incoming)))
+    }
+}
+
+/// Fails with [Error::OrderingCycle] if `listeners[index]` is part of a cycle.
+///
+/// Any cycle created by adding a single listener passes through it, so this is all that needs
+/// to be checked when inserting into a channel whose constraints were consistent.
+pub(crate) fn check(listeners: &[Listener], index: usize) -> Result<(), Error> {
+    // Without a successor there is no way back, which spares indexing the common case of a
+    // listener appended after the existing ones.
+    let listener = &listeners[index];
+    let leads = |(other, x)| other != index && precedes(listener, x);
+    if !listeners.iter().enumerate().any(leads) {
+        return Ok(());
+    }
+    let graph = Graph::new(listeners);
+
+    // Depth-first search for a path back to `index`, remembering how each listener was reached.
+    let mut parent = vec![None; listeners.len()];
+    let mut pending = vec![index];
+    while let Some(current) = pending.pop() {
+        for next in graph.successors(current) {
+            if next == index {
+                let mut cycle = vec![listeners[index].label];
+                let mut node = current;
+                while node != index {
+                    cycle.push(listeners[node].label);
+                    node = parent[node].unwrap();
+                }
+                cycle[1..].reverse();
+                cycle.push(listeners[index].label);
+                return Err(Error::OrderingCycle(cycle));
+            }
+            if parent[next].is_none() {
+                parent[next] = Some(current);
+                pending.push(next);
+            }
+        }
+    }
+    Ok(())
+}
+
+// Labels of a cycle among the listeners left with incoming edges after sorting.
+fn cycle(graph: &Graph, incoming: &[usize]) -> Vec<&'static str> {
+    let listeners = graph.listeners;
+
+    // Every remaining listener has a remaining predecessor, so walking backwards from any of
+    // them eventually revisits a listener.
+    let mut predecessor = vec![None; listeners.len()];
+    for from in (0..listeners.len()).filter(|x| incoming[*x] > 0) {
+        for to in graph.successors(from) {
+            predecessor[to] = Some(from);
+        }
+    }
+
+    let mut path = vec![(0..listeners.len()).find(|x| incoming[*x] > 0).unwrap()];
+    loop {
+        let previous = predecessor[*path.last().unwrap()].unwrap();
+        if let Some(start) = path.iter().position(|x| *x == previous) {
+            // The path runs against the edges, reverse it to list the cycle in emission order.
+            let first = listeners[path[start]].label;
+            let mut cycle = vec![first];
+            cycle.extend(path[start + 1..].iter().rev().map(|x| listeners[*x].label));
+            cycle.push(first);
+            return cycle;
+        }
+        path.push(previous);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn listener(sequence: isize, name: &'static str, constraint: Option<Constraint>) -> Listener {
+        Listener {
+            id: SubscriptionId {
+                relative: 0,
+                sequence,
+            },
+            name: Some(name),
+            label: name,
+            constraint,
+        }
+    }
+
+    fn names(listeners: &[Listener], order: Vec<SubscriptionId>) -> Vec<&'static str> {
+        order
+            .iter()
+            .map(|id| listeners.iter().find(|x| x.id == *id).unwrap().label)
+            .collect()
+    }
+
+    #[test]
+    fn constraints_override_identifiers() {
+        let make = || {
+            vec![
+                listener(1, "render", None),
+                listener(2, "input", Some(Constraint::Before("physics"))),
+                listener(3, "physics", Some(Constraint::Before("render"))),
+                listener(4, "audio", Some(Constraint::After("missing"))),
+            ]
+        };
+        let order = sort(make()).unwrap();
+        assert_eq!(
+            names(&make(), order),
+            ["input", "physics", "render", "audio"]
+        );
+    }
+
+    #[test]
+    fn reports_cycle() {
+        let listeners = vec![
+            listener(1, "a", Some(Constraint::After("c"))),
+            listener(2, "b", Some(Constraint::After("a"))),
+            listener(3, "c", Some(Constraint::After("b"))),
+            listener(4, "d", Some(Constraint::After("a"))),
+        ];
+        assert_eq!(
+            sort(listeners),
+            Err(Error::OrderingCycle(vec!["a", "b", "c", "a"]))
+        );
+    }
+
+    #[test]
+    fn check_reports_cycle_through_listener() {
+        let listeners = vec![
+            listener(1, "a", Some(Constraint::After("c"))),
+            listener(2, "b", Some(Constraint::After("a"))),
+            listener(3, "d", Some(Constraint::After("a"))),
+            listener(4, "c", Some(Constraint::After("b"))),
+        ];
+        assert_eq!(check(&listeners, 2), Ok(()));
+        assert_eq!(
+            check(&listeners, 3),
+            Err(Error::OrderingCycle(vec!["c", "a", "b", "c"]))
+        );
+    }
+}
@@ -0,0 +1,152 @@
+use std::{cell::RefCell, collections::VecDeque};
+
+/// First-in first-out queue of deferred events.
+///
+/// Emitting through [Channel](crate::Channel)s and [Slot](crate::Slot)s happens immediately and
+/// recursively. A queue allows handlers to instead [post](Queue::post) an event, which is
+/// dispatched later on by calling [flush](Queue::flush), typically from the top of the call
+/// stack where no node is borrowed.
+///
+/// ```
+/// use revent::{node_dyn, Channel, Node, Queue};
+///
+/// trait Damage {
+///     fn damage(&mut self, hub: &Hub, amount: u32);
+/// }
+///
+/// #[derive(Default)]
+/// struct Hub {
+///     damage: Channel<dyn Damage>,
+///     queue: Queue<Box<dyn FnOnce(&Hub)>>,
+/// }
+///
+/// struct Player {
+///     health: u32,
+/// }
+///
+/// impl Damage for Player {
+///     fn damage(&mut self, hub: &Hub, amount: u32) {
+///         self.health = self.health.saturating_sub(amount);
+///         if amount > 1 {
+///             // Dispatched after this handler returns instead of recursing.
+///             hub.queue.post(Box::new(move |hub| {
+///                 hub.damage.emit(|x| x.damage(hub, amount / 2));
+///             }));
+///         }
+///     }
+/// }
+///
+/// let hub = Hub::default();
+/// let player = Node::new(Player { health: 100 });
+/// hub.damage.insert(0, node_dyn!(player.clone(), dyn Damage));
+///
+/// hub.queue.post(Box::new(|hub| hub.damage.emit(|x| x.damage(hub, 8))));
+/// hub.queue.flush(|event| event(&hub));
+///
+/// player.emit(|x| assert_eq!(x.health, 100 - 8 - 4 - 2 - 1));
+/// ```
+pub struct Queue<E> {
+    events: RefCell<VecDeque<E>>,
+}
+
+impl<E> Default for Queue<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E> Queue<E> {
+    /// Create a new queue.
+    pub fn new() -> Self {
+        Self {
+            events: RefCell::new(VecDeque::new()),
+        }
+    }
+
+    /// Append an event to the end of this queue.
+    ///
+    /// Can be called from anywhere, including handlers of nodes that are not suspended, since
+    /// the event is not dispatched until [flush](Queue::flush) is called.
+    pub fn post(&self, event: E) {
+        self.events.borrow_mut().push_back(event);
+    }
+
+    /// Dispatch all events in this queue in the order they were posted.
+    ///
+    /// Events posted by `handler` while flushing are dispatched in the same call, after all
+    /// events that were posted before them. If `flush` is called from within `handler`, the
+    /// inner call continues draining the queue before the outer call resumes.
+    pub fn flush(&self, mut handler: impl FnMut(E)) {
+        loop {
+            // The queue must not be borrowed while `handler` runs, as it may post new events.
+            let event = self.events.borrow_mut().pop_front();
+            match event {
+                Some(event) => (handler)(event),
+                None => break,
+            }
+        }
+    }
+
+    /// Number of events waiting to be dispatched.
+    pub fn len(&self) -> usize {
+        self.events.borrow().len()
+    }
+
+    /// Returns true if no events are waiting to be dispatched.
+    pub fn is_empty(&self) -> bool {
+        self.events.borrow().is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[quickcheck_macros::quickcheck]
+    fn flush_is_fifo(events: Vec<u8>) {
+        let queue = Queue::new();
+
+        for event in events.iter() {
+            queue.post(*event);
+        }
+        assert_eq!(queue.len(), events.len());
+
+        let mut flushed = vec![];
+        queue.flush(|event| flushed.push(event));
+
+        assert_eq!(flushed, events);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn post_during_flush() {
+        let queue = Queue::new();
+        queue.post(3);
+        queue.post(10);
+
+        let mut flushed = vec![];
+        queue.flush(|event| {
+            if event > 0 {
+                queue.post(event - 1);
+            }
+            flushed.push(event);
+        });
+
+        assert_eq!(flushed, [3, 10, 2, 9, 1, 8, 0, 7, 6, 5, 4, 3, 2, 1, 0]);
+    }
+
+    #[test]
+    fn post_while_borrowed() {
+        let queue = Queue::new();
+        let node = Node::new(0);
+
+        node.emit(|x| {
+            *x += 1;
+            queue.post(());
+        });
+
+        // Dispatching from within the emit above would conflict, so it is done afterwards.
+        queue.flush(|()| node.emit(|x| *x += 1));
+        node.emit(|x| assert_eq!(*x, 2));
+    }
+}
@@ -1,4 +1,10 @@
-use crate::{Node, Trace};
+use crate::{
+    node::Link,
+    stack_dump,
+    subscription::{Attach, Subscription},
+    Error, Node, Trace, TraceKind, WeakNode,
+};
+use std::{any, fmt, rc::Weak};
 
 /// Container for a single optional [Node].
 ///
@@ -19,7 +25,10 @@ use crate::{Node, Trace};
 /// println!("{}", result);
 /// ```
 pub struct Slot<T: ?Sized> {
-    items: Option<Node<T>>,
+    items: Option<Link<T>>,
+    // Set when the node was inserted through a `Subscription`.
+    token: Option<Weak<()>>,
+    name: Option<&'static str>,
     trace: Trace,
 }
 
@@ -32,27 +41,185 @@ impl<T: ?Sized> Default for Slot<T> {
 impl<T: ?Sized> Slot<T> {
     /// Create a new slot.
     pub fn new() -> Self {
-        Self {
-            items: None,
-            trace: Trace::empty(),
-        }
+        Self::new_with_trace_object(None, Trace::empty())
+    }
+
+    /// Create a new slot with a name.
+    ///
+    /// The name is used instead of the type name in panic messages, traces and
+    /// [Debug](std::fmt::Debug) output.
+    ///
+    /// ```should_panic
+    /// use revent::Slot;
+    ///
+    /// let slot = Slot::<()>::named("hud");
+    ///
+    /// // Panics with "revent: emit: slot contains no element (in slot hud)".
+    /// slot.emit(|_| {});
+    /// ```
+    pub fn named(name: &'static str) -> Self {
+        Self::new_with_trace_object(Some(name), Trace::empty())
     }
 
     /// Create a new channel with a trace object.
     pub fn new_with_trace(trace: impl Fn(usize) + 'static) -> Self {
+        Self::new_with_trace_object(None, Trace::new(trace))
+    }
+
+    fn new_with_trace_object(name: Option<&'static str>, trace: Trace) -> Self {
         Self {
             items: None,
-            trace: Trace::new(trace),
+            token: None,
+            name,
+            trace,
         }
     }
 
+    /// Name given to this slot by [Slot::named], if any.
+    pub fn name(&self) -> Option<&'static str> {
+        self.name
+    }
+
     /// Insert a node into this slot.
     ///
+    /// A slot whose node was inserted weakly or through a [Subscription] is empty again once
+    /// that node or subscription is dropped.
+    ///
     /// # Panics #
     ///
-    /// Panics if there already exists a node in this slot.
+    /// Panics if there already exists a node in this slot, use [replace](Slot::replace) to
+    /// overwrite it.
+    ///
+    /// ```should_panic
+    /// use revent::{Node, Slot};
+    ///
+    /// let mut slot = Slot::new();
+    /// slot.insert(Node::new(1));
+    /// slot.insert(Node::new(2));
+    /// ```
     pub fn insert(&mut self, item: Node<T>) {
-        self.items = Some(item);
+        if let Err(error) = self.try_insert(item) {
+            panic!("revent: insert: {} (in slot {})", error, self.label());
+        }
+    }
+
+    /// Insert a node into this slot, returning an [Error] instead of panicking.
+    ///
+    /// Fails with [Error::OccupiedSlot] if there already exists a node in this slot, in which
+    /// case `item` is dropped and the slot is left untouched.
+    ///
+    /// ```
+    /// use revent::{Error, Node, Slot};
+    ///
+    /// let mut slot = Slot::new();
+    /// assert_eq!(slot.try_insert(Node::new(1)), Ok(()));
+    /// assert_eq!(slot.try_insert(Node::new(2)), Err(Error::OccupiedSlot));
+    /// assert_eq!(slot.emit(|x| *x), 1);
+    /// ```
+    pub fn try_insert(&mut self, item: Node<T>) -> Result<(), Error> {
+        if !self.is_empty() {
+            return Err(Error::OccupiedSlot);
+        }
+        self.replace(item);
+        Ok(())
+    }
+
+    /// Insert a node into this slot, returning the node it replaces, if any.
+    ///
+    /// Unlike the other insertion methods this never fails. A replaced node that was inserted
+    /// through a [Subscription] is detached from it, dropping that subscription no longer
+    /// empties the slot.
+    ///
+    /// ```
+    /// use revent::{Node, Slot};
+    ///
+    /// let mut slot = Slot::new();
+    /// assert!(slot.replace(Node::new(1)).is_none());
+    ///
+    /// let old = slot.replace(Node::new(2)).unwrap();
+    /// assert_eq!(old.emit(|x| *x), 1);
+    /// assert_eq!(slot.emit(|x| *x), 2);
+    /// ```
+    pub fn replace(&mut self, item: Node<T>) -> Option<Node<T>> {
+        let old = self.take();
+        self.items = Some(Link::Strong(item));
+        old
+    }
+
+    /// Insert a weak node into this slot.
+    ///
+    /// Once all strong [Node]s of `item` are dropped, the slot behaves as if it were empty.
+    ///
+    /// # Panics #
+    ///
+    /// Panics if there already exists a node in this slot.
+    pub fn insert_weak(&mut self, item: WeakNode<T>) {
+        if let Err(error) = self.try_insert_weak(item) {
+            panic!("revent: insert: {} (in slot {})", error, self.label());
+        }
+    }
+
+    /// Insert a weak node into this slot, returning an [Error] instead of panicking.
+    ///
+    /// Fails with [Error::OccupiedSlot] if there already exists a node in this slot.
+    ///
+    /// ```
+    /// use revent::{Error, Node, Slot};
+    ///
+    /// let mut slot = Slot::new();
+    /// let node = Node::new(1);
+    /// assert_eq!(slot.try_insert_weak(Node::downgrade(&node)), Ok(()));
+    /// assert_eq!(
+    ///     slot.try_insert_weak(Node::downgrade(&node)),
+    ///     Err(Error::OccupiedSlot)
+    /// );
+    ///
+    /// drop(node);
+    /// assert_eq!(slot.try_insert(Node::new(2)), Ok(()));
+    /// ```
+    pub fn try_insert_weak(&mut self, item: WeakNode<T>) -> Result<(), Error> {
+        if !self.is_empty() {
+            return Err(Error::OccupiedSlot);
+        }
+        self.take();
+        self.items = Some(Link::Weak(item));
+        Ok(())
+    }
+
+    /// Insert a node into this slot, returning a [Subscription] that empties the slot when
+    /// dropped.
+    ///
+    /// ```
+    /// use revent::{Node, Slot};
+    ///
+    /// let mut slot = Slot::new();
+    ///
+    /// let subscription = slot.subscribe(Node::new(123));
+    /// assert_eq!(slot.emit(|x| *x), 123);
+    ///
+    /// drop(subscription);
+    /// assert!(slot.try_emit(|x| *x).is_err());
+    /// ```
+    ///
+    /// # Panics #
+    ///
+    /// Panics if there already exists a node in this slot.
+    pub fn subscribe(&mut self, item: Node<T>) -> Subscription {
+        match self.try_subscribe(item) {
+            Ok(subscription) => subscription,
+            Err(error) => panic!("revent: insert: {} (in slot {})", error, self.label()),
+        }
+    }
+
+    /// Subscribe a node to this slot, returning an [Error] instead of panicking.
+    ///
+    /// Fails with [Error::OccupiedSlot] if there already exists a node in this slot, in which
+    /// case `item` is dropped and the slot is left untouched.
+    pub fn try_subscribe(&mut self, item: Node<T>) -> Result<Subscription, Error> {
+        self.try_insert(item)?;
+        let subscription = Subscription::detached();
+        self.token = Some(subscription.token());
+        Ok(subscription)
     }
 
     /// Remove the currently held node from this slot.
@@ -61,7 +228,55 @@ impl<T: ?Sized> Slot<T> {
     ///
     /// Panics if there exists no node in this slot.
     pub fn remove(&mut self) -> Node<T> {
-        self.items.take().unwrap()
+        self.take().unwrap()
+    }
+
+    /// Remove the currently held node from this slot, returning `None` if the slot is empty.
+    pub fn take(&mut self) -> Option<Node<T>> {
+        let active = self.is_active();
+        self.token = None;
+        self.items
+            .take()
+            .filter(|_| active)
+            .and_then(|x| x.upgrade())
+    }
+
+    /// Get the node held by this slot, if any.
+    ///
+    /// ```
+    /// use revent::{Node, Slot};
+    ///
+    /// let mut slot = Slot::new();
+    /// assert!(slot.get().is_none());
+    ///
+    /// let node = Node::new(123);
+    /// slot.insert(node.clone());
+    /// assert!(Node::ptr_eq(&slot.get().unwrap(), &node));
+    /// ```
+    pub fn get(&self) -> Option<Node<T>> {
+        self.items
+            .as_ref()
+            .filter(|_| self.is_active())
+            .and_then(Link::upgrade)
+    }
+
+    /// Returns true if this slot holds no node.
+    ///
+    /// ```
+    /// use revent::{Node, Slot};
+    ///
+    /// let mut slot = Slot::new();
+    /// assert!(slot.is_empty());
+    ///
+    /// let node = Node::new(123);
+    /// slot.insert_weak(Node::downgrade(&node));
+    /// assert!(!slot.is_empty());
+    ///
+    /// drop(node);
+    /// assert!(slot.is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        !self.is_active() || !self.items.as_ref().is_some_and(Link::is_alive)
     }
 
     /// Apply a function to the node in this slot.
@@ -70,18 +285,138 @@ impl<T: ?Sized> Slot<T> {
     ///
     /// Panics if there exists no node in this slot.
     pub fn emit<R>(&self, handler: impl FnOnce(&mut T) -> R) -> R {
+        match self.try_emit(handler) {
+            Ok(value) => value,
+            Err(error) => panic!(
+                "revent: emit: {} (in slot {}){}",
+                error,
+                self.label(),
+                stack_dump(&error)
+            ),
+        }
+    }
+
+    /// Apply a function to the node in this slot, returning `default` if the slot is empty.
+    ///
+    /// ```
+    /// use revent::{Node, Slot};
+    ///
+    /// let mut slot = Slot::new();
+    /// assert_eq!(slot.emit_or(0, |x| *x + 1), 0);
+    ///
+    /// slot.insert(Node::new(1));
+    /// assert_eq!(slot.emit_or(0, |x| *x + 1), 2);
+    /// ```
+    ///
+    /// # Panics #
+    ///
+    /// Panics if the node in this slot is already borrowed.
+    pub fn emit_or<R>(&self, default: R, handler: impl FnOnce(&mut T) -> R) -> R {
+        match self.try_emit(handler) {
+            Ok(value) => value,
+            Err(Error::EmptySlot) => default,
+            Err(error) => panic!(
+                "revent: emit: {} (in slot {}){}",
+                error,
+                self.label(),
+                stack_dump(&error)
+            ),
+        }
+    }
+
+    /// Apply a function to the node in this slot, returning an [Error] instead of panicking.
+    ///
+    /// Fails with [Error::EmptySlot] if there exists no node in this slot, or with
+    /// [Error::AlreadyBorrowed] if the node is already borrowed. Use [Result::ok] to only
+    /// distinguish whether the handler ran.
+    pub fn try_emit<R>(&self, handler: impl FnOnce(&mut T) -> R) -> Result<R, Error> {
+        self.visit(|value| value.try_emit(|x| (handler)(x)))
+    }
+
+    /// Apply a function to a shared borrow of the node in this slot, see [Node::emit_ref].
+    ///
+    /// ```
+    /// use revent::{Node, Slot};
+    ///
+    /// let mut slot = Slot::new();
+    /// slot.insert(Node::new(1));
+    ///
+    /// assert_eq!(slot.emit_ref(|x| slot.emit_ref(|y| *x + *y)), 2);
+    /// ```
+    ///
+    /// # Panics #
+    ///
+    /// Panics if there exists no node in this slot, or if the node is already mutably borrowed.
+    pub fn emit_ref<R>(&self, handler: impl FnOnce(&T) -> R) -> R {
+        match self.try_emit_ref(handler) {
+            Ok(value) => value,
+            Err(error) => panic!(
+                "revent: emit: {} (in slot {}){}",
+                error,
+                self.label(),
+                stack_dump(&error)
+            ),
+        }
+    }
+
+    /// Apply a function to a shared borrow of the node in this slot, returning an [Error]
+    /// instead of panicking.
+    pub fn try_emit_ref<R>(&self, handler: impl FnOnce(&T) -> R) -> Result<R, Error> {
+        self.visit(|value| value.try_emit_ref(|x| (handler)(x)))
+    }
+
+    // Emits the node with `emit`, which returns `None` for a dropped weak node.
+    fn visit<R>(
+        &self,
+        emit: impl FnOnce(&Link<T>) -> Option<Result<R, Error>>,
+    ) -> Result<R, Error> {
         self.trace.log();
-        Trace::indent();
+        let address: *const Self = self;
+        let _indent = Trace::indent(
+            TraceKind::Slot,
+            address as *const () as usize,
+            self.name,
+            any::type_name::<T>(),
+        );
+
+        self.items
+            .as_ref()
+            .filter(|_| self.is_active())
+            .and_then(emit)
+            .unwrap_or(Err(Error::EmptySlot))
+    }
 
-        let value = if let Some(value) = self.items.as_ref() {
-            value.emit(|x| (handler)(x))
-        } else {
-            panic!("revent: emit: slot contains no element");
-        };
+    fn is_active(&self) -> bool {
+        self.token
+            .as_ref()
+            .map_or(true, |token| token.strong_count() > 0)
+    }
 
-        Trace::dedent();
+    fn label(&self) -> &'static str {
+        self.name.unwrap_or_else(any::type_name::<T>)
+    }
+}
 
-        value
+impl<T: ?Sized> fmt::Debug for Slot<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Slot")
+            .field("name", &self.name)
+            .field("type_name", &any::type_name::<T>())
+            .field("node", &self.get())
+            .finish()
+    }
+}
+
+impl<T: ?Sized> Attach for Slot<T> {
+    type Item = T;
+
+    /// Subscribe a node to this slot.
+    ///
+    /// # Panics #
+    ///
+    /// Panics if there already exists a node in this slot, see [Slot::subscribe].
+    fn attach(&mut self, item: Node<T>) -> Subscription {
+        self.subscribe(item)
     }
 }
 
@@ -128,6 +463,103 @@ mod tests {
         let slot = Slot::<()>::new();
         slot.emit(|_| {});
     }
+
+    #[test]
+    fn debug_shows_name_and_node() {
+        let mut slot = Slot::named("hud");
+        assert_eq!(
+            format!("{:?}", slot),
+            r#"Slot { name: Some("hud"), type_name: "u8", node: None }"#
+        );
+
+        let node = Node::named("health", 0u8);
+        slot.insert(node.clone());
+        node.emit(|_| {
+            assert_eq!(
+                format!("{:?}", slot),
+                r#"Slot { name: Some("hud"), type_name: "u8", node: Some(Node { name: Some("health"), type_name: "u8", borrowed: true }) }"#
+            );
+        });
+    }
+
+    #[test]
+    fn weak_expires() {
+        let mut slot = Slot::new();
+
+        let node = Node::new(1);
+        slot.insert_weak(Node::downgrade(&node));
+        assert_eq!(slot.try_emit(|x| *x), Ok(1));
+
+        drop(node);
+        assert_eq!(slot.try_emit(|x| *x), Err(Error::EmptySlot));
+    }
+
+    #[test]
+    fn subscription_drop_empties() {
+        let mut slot = Slot::new();
+
+        let first = slot.subscribe(Node::new(1));
+        assert_eq!(first.id(), None);
+        assert_eq!(slot.try_emit(|x| *x), Ok(1));
+
+        // Replacing the node detaches it from the earlier subscription.
+        assert!(slot.replace(Node::new(2)).is_some());
+        drop(first);
+        assert_eq!(slot.try_emit(|x| *x), Ok(2));
+
+        assert_eq!(
+            slot.try_subscribe(Node::new(3)).err(),
+            Some(Error::OccupiedSlot)
+        );
+        assert_eq!(slot.take().map(|x| x.emit(|x| *x)), Some(2));
+        let second = slot.subscribe(Node::new(3));
+        drop(second);
+        assert_eq!(slot.try_emit(|x| *x), Err(Error::EmptySlot));
+    }
+
+    #[test]
+    #[should_panic(expected = "revent: insert: slot already contains an element (in slot hud)")]
+    fn insert_into_occupied() {
+        let mut slot = Slot::named("hud");
+        slot.insert(Node::new(1));
+        slot.insert(Node::new(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "revent: insert: slot already contains an element (in slot hud)")]
+    fn subscribe_to_occupied() {
+        let mut slot = Slot::named("hud");
+        let node = Node::new(1);
+        slot.insert_weak(Node::downgrade(&node));
+        let _subscription = slot.subscribe(Node::new(2));
+    }
+
+    #[test]
+    fn take_empties() {
+        let mut slot = Slot::new();
+        assert!(slot.take().is_none());
+
+        let subscription = slot.subscribe(Node::new(1));
+        assert_eq!(slot.take().map(|x| x.emit(|x| *x)), Some(1));
+        assert!(slot.is_empty());
+        drop(subscription);
+
+        let subscription = slot.subscribe(Node::new(2));
+        drop(subscription);
+        assert!(slot.is_empty());
+        assert!(slot.take().is_none());
+        slot.insert(Node::new(3));
+        assert_eq!(slot.emit_or(0, |x| *x), 3);
+    }
+
+    #[test]
+    fn try_emit_without_insert() {
+        let mut slot = Slot::new();
+        assert_eq!(slot.try_emit(|_| {}), Err(Error::EmptySlot));
+
+        slot.insert(Node::new(1));
+        assert_eq!(slot.try_emit(|x| *x), Ok(1));
+    }
 }
 
 #[cfg(all(test, feature = "trace"))]
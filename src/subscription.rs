@@ -0,0 +1,170 @@
+use crate::Node;
+use std::{
+    cell::RefCell,
+    rc::{Rc, Weak},
+};
+
+/// Identifies a single insertion of a [Node](crate::Node) into a [Channel](crate::Channel).
+///
+/// Inserting the same node twice yields two distinct identifiers, so each registration can be
+/// removed on its own using [Channel::unsubscribe](crate::Channel::unsubscribe).
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct SubscriptionId {
+    pub(crate) relative: isize,
+    pub(crate) sequence: isize,
+}
+
+pub(crate) type Expired = Rc<RefCell<Vec<SubscriptionId>>>;
+
+/// Guard that unsubscribes a node from a [Channel](crate::Channel) or [Slot](crate::Slot) when
+/// dropped.
+///
+/// Created by [Channel::subscribe](crate::Channel::subscribe) and
+/// [Slot::subscribe](crate::Slot::subscribe). The node stops receiving emissions as soon as
+/// this guard is dropped.
+#[must_use = "dropping a subscription immediately unsubscribes its node"]
+pub struct Subscription {
+    // Entries in the container hold a `Weak` to this token to check if they are still active.
+    token: Rc<()>,
+    // Only channels remove entries by identifier, a slot merely checks the token.
+    channel: Option<(Weak<RefCell<Vec<SubscriptionId>>>, SubscriptionId)>,
+}
+
+impl Subscription {
+    pub(crate) fn new(expired: &Expired, id: SubscriptionId) -> Self {
+        Self {
+            token: Rc::new(()),
+            channel: Some((Rc::downgrade(expired), id)),
+        }
+    }
+
+    pub(crate) fn detached() -> Self {
+        Self {
+            token: Rc::new(()),
+            channel: None,
+        }
+    }
+
+    pub(crate) fn token(&self) -> Weak<()> {
+        Rc::downgrade(&self.token)
+    }
+
+    /// Identifier of the channel registration held by this guard, `None` for a slot.
+    pub fn id(&self) -> Option<SubscriptionId> {
+        self.channel.as_ref().map(|(_, id)| *id)
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        // The channel removes expired entries after its next emission or mutation.
+        if let Some((expired, id)) = &self.channel {
+            if let Some(expired) = expired.upgrade() {
+                expired.borrow_mut().push(*id);
+            }
+        }
+    }
+}
+
+/// Collection of [Subscription]s which are all dropped together.
+///
+/// Returned by [Subscriber::subscribe] to unsubscribe a node from every container of a hub at
+/// once.
+#[derive(Default)]
+#[must_use = "dropping subscriptions immediately unsubscribes their nodes"]
+pub struct Subscriptions {
+    subscriptions: Vec<Subscription>,
+}
+
+impl Subscriptions {
+    /// Create an empty collection.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a subscription to this collection.
+    pub fn push(&mut self, subscription: Subscription) {
+        self.subscriptions.push(subscription);
+    }
+
+    /// Number of subscriptions in this collection.
+    pub fn len(&self) -> usize {
+        self.subscriptions.len()
+    }
+
+    /// Returns true if this collection holds no subscriptions.
+    pub fn is_empty(&self) -> bool {
+        self.subscriptions.is_empty()
+    }
+}
+
+impl Extend<Subscription> for Subscriptions {
+    fn extend<I: IntoIterator<Item = Subscription>>(&mut self, iter: I) {
+        self.subscriptions.extend(iter);
+    }
+}
+
+/// Container a node can be [attach](Attach::attach)ed to, implemented by
+/// [Channel](crate::Channel) and [Slot](crate::Slot).
+///
+/// Allows [Subscriber] implementations to treat all containers of a hub the same way.
+pub trait Attach {
+    /// Type of the nodes held by this container.
+    type Item: ?Sized;
+
+    /// Subscribe a node to this container using default settings.
+    fn attach(&mut self, item: Node<Self::Item>) -> Subscription;
+}
+
+/// Type whose nodes can subscribe to every container of a hub `H` it listens to.
+///
+/// Usually implemented using `#[derive(Subscriber)]` when the `derive` feature is enabled.
+///
+/// ```
+/// use revent::{node_dyn, Attach, Channel, Node, Slot, Subscriber, Subscriptions};
+///
+/// trait Tick {
+///     fn tick(&mut self);
+/// }
+/// trait Render {
+///     fn render(&mut self);
+/// }
+///
+/// #[derive(Default)]
+/// struct Hub {
+///     tick: Channel<dyn Tick>,
+///     render: Slot<dyn Render>,
+/// }
+///
+/// struct Player;
+///
+/// impl Tick for Player {
+///     fn tick(&mut self) {}
+/// }
+/// impl Render for Player {
+///     fn render(&mut self) {}
+/// }
+///
+/// impl Subscriber<Hub> for Player {
+///     fn subscribe(node: &Node<Self>, hub: &mut Hub) -> Subscriptions {
+///         let mut subscriptions = Subscriptions::new();
+///         subscriptions.push(hub.tick.attach(node_dyn!(node.clone(), dyn Tick)));
+///         subscriptions.push(hub.render.attach(node_dyn!(node.clone(), dyn Render)));
+///         subscriptions
+///     }
+/// }
+///
+/// let mut hub = Hub::default();
+/// let player = Node::new(Player);
+///
+/// let subscriptions = Player::subscribe(&player, &mut hub);
+/// assert_eq!(subscriptions.len(), 2);
+/// hub.render.emit(|x| x.render());
+///
+/// drop(subscriptions);
+/// assert!(hub.render.try_emit(|x| x.render()).is_err());
+/// ```
+pub trait Subscriber<H>: Sized {
+    /// Subscribe `node` to every container of `hub` this type listens to.
+    fn subscribe(node: &Node<Self>, hub: &mut H) -> Subscriptions;
+}
@@ -0,0 +1,821 @@
+use crate::{
+    borrow_mut, is_borrowed, max_depth, stack_dump, subscription::SubscriptionId, type_id,
+    BorrowFlag, EmitGuard, Error, Frame, Trace, TraceKind, STACK,
+};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use std::{
+    any::{self, TypeId},
+    cell::{Cell, UnsafeCell},
+    collections::BTreeMap,
+    fmt, ptr,
+    sync::{
+        atomic::{AtomicIsize, AtomicUsize, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+};
+#[cfg(feature = "nightly")]
+use std::{marker::Unsize, ops::CoerceUnsized};
+
+/// Thread-safe counterpart of [Node](crate::Node).
+///
+/// Can be sent to and shared between threads if its contents are [Send]. Whichever thread
+/// emits the node first owns it until that emission returns, including while the node is
+/// [suspend](crate::Suspend::suspend)ed, so suspending only allows the owning thread to emit it
+/// again. Emitting a node owned by another thread never blocks, it fails with
+/// [Error::HeldByOtherThread] instead, so threads emitting each other's nodes can not deadlock.
+///
+/// Sync nodes share the node stack of the emitting thread with [Node](crate::Node)s, so both
+/// kinds can be nested and suspended within each other.
+///
+/// ```
+/// use revent::{Suspend, SyncNode};
+/// use std::thread;
+///
+/// let node = SyncNode::new(0);
+///
+/// let worker = node.clone();
+/// thread::spawn(move || {
+///     worker.emit(|x| {
+///         *x += 1;
+///         x.suspend(|| worker.emit(|x| *x += 1));
+///     });
+/// })
+/// .join()
+/// .unwrap();
+///
+/// assert_eq!(node.emit(|x| *x), 2);
+/// ```
+pub struct SyncNode<T: ?Sized> {
+    item: Arc<Inner<T>>,
+    // Type of the object stored in this node at creation, before any unsizing coercion.
+    id: TypeId,
+    type_name: &'static str,
+}
+
+#[cfg(feature = "nightly")]
+impl<T, U> CoerceUnsized<SyncNode<U>> for SyncNode<T>
+where
+    T: Unsize<U> + ?Sized,
+    U: ?Sized,
+{
+}
+
+struct Inner<T: ?Sized> {
+    // Address of the `STACK` of the owning thread, or zero if no thread owns this node.
+    owner: AtomicUsize,
+    // Only accessed by the owning thread, the `owner` handover orders these accesses.
+    flag: Cell<BorrowFlag>,
+    // Number of emissions of this node in progress on the owning thread, including suspended
+    // ones. Ownership is released once it drops to zero.
+    emissions: Cell<usize>,
+    name: Option<&'static str>,
+    data: UnsafeCell<T>,
+}
+
+// unsafe: `data`, `flag` and `emissions` are only accessed by the thread owning the node, which
+// is exclusive thanks to `owner`. Handing over `&mut T` to other threads requires `T: Send`.
+unsafe impl<T: ?Sized + Send> Send for SyncNode<T> {}
+unsafe impl<T: ?Sized + Send> Sync for SyncNode<T> {}
+
+/// Convert a `SyncNode<A>` into a `SyncNode<dyn Trait>`, see [node_dyn](crate::node_dyn).
+///
+/// ```
+/// use revent::{sync_node_dyn, SyncChannel, SyncNode};
+///
+/// trait Trait: Send {
+///     fn function(&mut self);
+/// }
+///
+/// struct Object;
+/// impl Trait for Object {
+///     fn function(&mut self) {}
+/// }
+///
+/// let channel = SyncChannel::<dyn Trait>::new();
+/// channel.insert(0, sync_node_dyn!(SyncNode::new(Object), dyn Trait));
+/// ```
+#[macro_export]
+macro_rules! sync_node_dyn {
+    ($node:expr, $type:ty $(,)?) => {
+        match $node {
+            // unsafe: the closure is only an unsizing coercion of the data pointer.
+            node => unsafe { $crate::SyncNode::__coerce(node, |x| -> *const $type { x }) },
+        }
+    };
+}
+
+// Releases ownership of a node once its last emission on the owning thread returns.
+struct Ownership<'a> {
+    emissions: &'a Cell<usize>,
+    owner: &'a AtomicUsize,
+}
+
+impl<'a> Ownership<'a> {
+    fn new(emissions: &'a Cell<usize>, owner: &'a AtomicUsize) -> Self {
+        emissions.set(emissions.get() + 1);
+        Self { emissions, owner }
+    }
+}
+
+impl Drop for Ownership<'_> {
+    fn drop(&mut self) {
+        self.emissions.set(self.emissions.get() - 1);
+        if self.emissions.get() == 0 {
+            self.owner.store(0, Ordering::Release);
+        }
+    }
+}
+
+impl<T: ?Sized> Clone for SyncNode<T> {
+    fn clone(&self) -> Self {
+        Self {
+            item: self.item.clone(),
+            id: self.id,
+            type_name: self.type_name,
+        }
+    }
+}
+
+impl<T> SyncNode<T> {
+    /// Create a new node.
+    pub fn new(item: T) -> Self {
+        Self::new_with_name(item, None)
+    }
+
+    /// Create a new node with a name, see [Node::named](crate::Node::named).
+    pub fn named(name: &'static str, item: T) -> Self {
+        Self::new_with_name(item, Some(name))
+    }
+
+    fn new_with_name(item: T, name: Option<&'static str>) -> Self {
+        Self {
+            item: Arc::new(Inner {
+                owner: AtomicUsize::new(0),
+                flag: Cell::new(0),
+                emissions: Cell::new(0),
+                name,
+                data: UnsafeCell::new(item),
+            }),
+            id: type_id::<T>(),
+            type_name: any::type_name::<T>(),
+        }
+    }
+}
+
+impl<T: ?Sized> SyncNode<T> {
+    /// Acquire a `&mut` to the contents of the node and allow it to
+    /// [Suspend](crate::Suspend) itself.
+    ///
+    /// # Panics #
+    ///
+    /// Panics if the node has already been accessed on this thread without being suspended, or
+    /// if another thread is emitting it.
+    pub fn emit<F: FnOnce(&mut T) -> R, R>(&self, handler: F) -> R {
+        match self.try_emit(handler) {
+            Ok(data) => data,
+            Err(error) => panic!("revent: emit: {}{}", error, stack_dump(&error)),
+        }
+    }
+
+    /// Acquire a `&mut` to the contents of the node, returning an [Error] instead of panicking.
+    ///
+    /// `handler` is only invoked if the node could be borrowed.
+    ///
+    /// ```
+    /// use revent::{Error, SyncNode};
+    /// use std::{sync::mpsc, thread};
+    ///
+    /// let node = SyncNode::named("shared", 0);
+    /// let (sender, receiver) = mpsc::channel();
+    ///
+    /// node.emit(|_| {
+    ///     let worker = node.clone();
+    ///     thread::spawn(move || sender.send(worker.try_emit(|_| {})).unwrap());
+    ///
+    ///     assert_eq!(
+    ///         receiver.recv().unwrap(),
+    ///         Err(Error::HeldByOtherThread(vec!["shared"]))
+    ///     );
+    /// });
+    /// ```
+    pub fn try_emit<F: FnOnce(&mut T) -> R, R>(&self, handler: F) -> Result<R, Error> {
+        let stack = STACK.with(|x| x.get());
+        let inner = &*self.item;
+
+        match inner
+            .owner
+            .compare_exchange(0, stack as usize, Ordering::Acquire, Ordering::Relaxed)
+        {
+            Ok(_) => {}
+            Err(owner) if owner == stack as usize => {}
+            // unsafe: `STACK` is only borrowed for immediate inspection.
+            Err(_) => return Err(Error::HeldByOtherThread(self.chain(unsafe { &*stack }))),
+        }
+        let _ownership = Ownership::new(&inner.emissions, &inner.owner);
+
+        if is_borrowed(&inner.flag) {
+            // unsafe: `STACK` is only borrowed for immediate inspection.
+            return Err(Error::AlreadyBorrowed(self.chain(unsafe { &*stack })));
+        }
+        if let Some(depth) = max_depth() {
+            // unsafe: `STACK` is only borrowed for immediate inspection.
+            let frames = unsafe { &*stack };
+            if frames.len() >= depth {
+                return Err(Error::DepthLimit(self.chain(frames)));
+            }
+        }
+        borrow_mut(&inner.flag);
+        // Declared before the guard and therefore dropped after it, so the exit is recorded once
+        // the frame is popped, at the same stack depth as the enter.
+        let flag: *const _ = &inner.flag;
+        let _trace = Trace::span(TraceKind::Node, flag as usize, self.name(), self.type_name);
+
+        // unsafe: `STACK` is never borrowed for longer than immediate mutation.
+        unsafe { &mut *stack }.push(Frame {
+            flag,
+            item: inner.data.get() as *mut _,
+            id: self.id,
+            name: self.name(),
+            type_name: self.type_name,
+            shared: false,
+        });
+
+        // Pops the stack and releases the borrow when leaving this scope, including when
+        // `handler` panics. Dropped before `_ownership`, which may hand the node to other threads.
+        let _guard = EmitGuard::new(&inner.flag, stack, false);
+
+        // unsafe: This thread owns the node and it is not borrowed, as checked above.
+        let object = unsafe { &mut *inner.data.get() };
+        Ok((handler)(object))
+    }
+
+    // Labels of `frames` followed by the label of this node.
+    fn chain(&self, frames: &[Frame]) -> Vec<&'static str> {
+        frames
+            .iter()
+            .map(Frame::label)
+            .chain(Some(self.label()))
+            .collect()
+    }
+
+    /// Name given to this node by [SyncNode::named], if any.
+    pub fn name(&self) -> Option<&'static str> {
+        self.item.name
+    }
+
+    /// Name of this node, or the type name of its contents if it is unnamed.
+    pub fn label(&self) -> &'static str {
+        self.name().unwrap_or(self.type_name)
+    }
+
+    /// Returns true if two `SyncNode`s point to the same allocation.
+    #[inline]
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        Arc::ptr_eq(&this.item, &other.item)
+    }
+
+    /// Implementation detail of [sync_node_dyn].
+    ///
+    /// # Safety #
+    ///
+    /// `coerce` must return its argument unchanged, apart from unsizing it to `U`.
+    #[doc(hidden)]
+    pub unsafe fn __coerce<U: ?Sized>(this: Self, coerce: fn(*const T) -> *const U) -> SyncNode<U> {
+        let SyncNode {
+            item,
+            id,
+            type_name,
+        } = this;
+
+        // See `Node::__coerce`.
+        let inner = Arc::into_raw(item);
+        let data = UnsafeCell::raw_get(ptr::addr_of!((*inner).data)).cast_const();
+        let offset = data.cast::<u8>().offset_from(inner.cast::<u8>()) as usize;
+        let inner = coerce(data).byte_sub(offset) as *const Inner<U>;
+
+        SyncNode {
+            item: Arc::from_raw(inner),
+            id,
+            type_name,
+        }
+    }
+}
+
+impl<T: ?Sized> fmt::Debug for SyncNode<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SyncNode")
+            .field("name", &self.name())
+            .field("type_name", &self.type_name)
+            .field("owned", &(self.item.owner.load(Ordering::Relaxed) != 0))
+            .finish()
+    }
+}
+
+// ---
+
+/// Thread-safe counterpart of [Channel](crate::Channel).
+///
+/// Emissions iterate over the nodes as they were when the emission started. Inserting or
+/// removing nodes, also from within a handler or from another thread, replaces the list for
+/// subsequent emissions instead of mutating it.
+///
+/// ```
+/// use revent::{SyncChannel, SyncNode};
+/// use std::{sync::Arc, thread};
+///
+/// let channel = Arc::new(SyncChannel::new());
+/// let node = SyncNode::new(0);
+/// channel.insert(0, node.clone());
+///
+/// let workers = (0..4)
+///     .map(|_| {
+///         let channel = channel.clone();
+///         thread::spawn(move || {
+///             // Emissions racing with another thread fail instead of blocking.
+///             while channel.try_emit(|x| *x += 1).is_err() {}
+///         })
+///     })
+///     .collect::<Vec<_>>();
+///
+/// for worker in workers {
+///     worker.join().unwrap();
+/// }
+/// assert_eq!(node.emit(|x| *x), 4);
+/// ```
+pub struct SyncChannel<T: ?Sized> {
+    items: Mutex<Arc<BTreeMap<SubscriptionId, SyncNode<T>>>>,
+    sequence: AtomicIsize,
+    name: Option<&'static str>,
+}
+
+impl<T: ?Sized> Default for SyncChannel<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: ?Sized> SyncChannel<T> {
+    /// Create a new channel.
+    pub fn new() -> Self {
+        Self::new_with_name(None)
+    }
+
+    /// Create a new channel with a name, see [Channel::named](crate::Channel::named).
+    pub fn named(name: &'static str) -> Self {
+        Self::new_with_name(Some(name))
+    }
+
+    fn new_with_name(name: Option<&'static str>) -> Self {
+        Self {
+            items: Mutex::new(Arc::new(BTreeMap::new())),
+            sequence: AtomicIsize::new(0),
+            name,
+        }
+    }
+
+    /// Name given to this channel by [SyncChannel::named], if any.
+    pub fn name(&self) -> Option<&'static str> {
+        self.name
+    }
+
+    /// Insert a node into this channel.
+    ///
+    /// Ordering follows the same rules as [Channel::insert](crate::Channel::insert).
+    pub fn insert(&self, relative: isize, item: SyncNode<T>) -> SubscriptionId {
+        let sequence = self.sequence.fetch_add(1, Ordering::Relaxed) + 1;
+        let sequence = if relative >= 0 { sequence } else { -sequence };
+        let id = SubscriptionId { relative, sequence };
+        Arc::make_mut(&mut self.lock()).insert(id, item);
+        id
+    }
+
+    /// Remove all insertions of a node from this channel.
+    pub fn remove(&self, item: &SyncNode<T>) {
+        Arc::make_mut(&mut self.lock()).retain(|_, x| !SyncNode::ptr_eq(x, item));
+    }
+
+    /// Remove a single insertion, returns its node if it was still part of this channel.
+    pub fn unsubscribe(&self, id: SubscriptionId) -> Option<SyncNode<T>> {
+        let mut items = self.lock();
+        if !items.contains_key(&id) {
+            return None;
+        }
+        Arc::make_mut(&mut items).remove(&id)
+    }
+
+    /// Apply a function to each item in this channel.
+    ///
+    /// # Panics #
+    ///
+    /// Panics if any node in this channel is already borrowed or emitted by another thread.
+    pub fn emit(&self, handler: impl FnMut(&mut T)) {
+        if let Err(error) = self.try_emit(handler) {
+            panic!(
+                "revent: emit: {} (in channel {}){}",
+                error,
+                self.label(),
+                stack_dump(&error)
+            );
+        }
+    }
+
+    /// Apply a function to each item in this channel, returning an [Error] instead of
+    /// panicking.
+    ///
+    /// Emission stops at the first node that can not be borrowed, nodes before it will have
+    /// been visited.
+    pub fn try_emit(&self, mut handler: impl FnMut(&mut T)) -> Result<(), Error> {
+        let address: *const Self = self;
+        let _indent = Trace::indent(
+            TraceKind::Channel,
+            address as *const () as usize,
+            self.name,
+            any::type_name::<T>(),
+        );
+
+        // The lock is released before emitting, so handlers can change this channel.
+        let items = self.lock().clone();
+        for item in items.values() {
+            item.try_emit(&mut handler)?;
+        }
+        Ok(())
+    }
+
+    /// Apply a function to each item in this channel, visiting items in parallel.
+    ///
+    /// Items inserted with the same `relative` value form a group whose items are emitted
+    /// concurrently on the [rayon] thread pool. Groups are emitted one after the other in the
+    /// order of their `relative` values, so a listener may still rely on running after all
+    /// listeners of a lower group.
+    ///
+    /// Each item is emitted on whichever thread visits it, see [SyncNode]. Only available with
+    /// the `rayon` feature.
+    ///
+    /// ```
+    /// use revent::{SyncChannel, SyncNode};
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    ///
+    /// let channel = SyncChannel::new();
+    /// for number in 0..8 {
+    ///     channel.insert(number / 4, SyncNode::new(number));
+    /// }
+    ///
+    /// let order = AtomicUsize::new(0);
+    /// channel.par_emit(|x| {
+    ///     let position = order.fetch_add(1, Ordering::SeqCst);
+    ///     // All items of the first group run before any item of the second.
+    ///     assert_eq!(position < 4, *x < 4);
+    /// });
+    /// ```
+    ///
+    /// # Panics #
+    ///
+    /// Panics if any node in this channel is already borrowed or emitted by another thread,
+    /// which includes a node inserted more than once into the same group.
+    #[cfg(feature = "rayon")]
+    pub fn par_emit(&self, handler: impl Fn(&mut T) + Sync)
+    where
+        T: Send,
+    {
+        if let Err(error) = self.try_par_emit(handler) {
+            panic!(
+                "revent: emit: {} (in channel {}){}",
+                error,
+                self.label(),
+                stack_dump(&error)
+            );
+        }
+    }
+
+    /// Apply a function to each item in this channel in parallel, returning an [Error] instead
+    /// of panicking.
+    ///
+    /// Emission stops after the group containing a node that can not be borrowed, other items
+    /// of that group may or may not have been visited.
+    #[cfg(feature = "rayon")]
+    pub fn try_par_emit(&self, handler: impl Fn(&mut T) + Sync) -> Result<(), Error>
+    where
+        T: Send,
+    {
+        let address: *const Self = self;
+        let _indent = Trace::indent(
+            TraceKind::Channel,
+            address as *const () as usize,
+            self.name,
+            any::type_name::<T>(),
+        );
+
+        let items = self.lock().clone();
+        let items = items.iter().collect::<Vec<_>>();
+        for group in items.chunk_by(|(a, _), (b, _)| a.relative == b.relative) {
+            group
+                .par_iter()
+                .try_for_each(|(_, item)| item.try_emit(&handler))?;
+        }
+        Ok(())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Arc<BTreeMap<SubscriptionId, SyncNode<T>>>> {
+        // No code that can panic runs while the lock is held.
+        self.items.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn label(&self) -> &'static str {
+        self.name.unwrap_or_else(any::type_name::<T>)
+    }
+}
+
+impl<T: ?Sized> fmt::Debug for SyncChannel<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SyncChannel")
+            .field("name", &self.name)
+            .field("type_name", &any::type_name::<T>())
+            .field("len", &self.lock().len())
+            .finish()
+    }
+}
+
+// ---
+
+/// Thread-safe counterpart of [Slot](crate::Slot).
+///
+/// ```
+/// use revent::{SyncNode, SyncSlot};
+/// use std::thread;
+///
+/// let mut slot = SyncSlot::new();
+/// slot.insert(SyncNode::new(123));
+///
+/// let result = thread::spawn(move || slot.emit(|x| *x + 1)).join().unwrap();
+/// assert_eq!(result, 124);
+/// ```
+pub struct SyncSlot<T: ?Sized> {
+    item: Option<SyncNode<T>>,
+    name: Option<&'static str>,
+}
+
+impl<T: ?Sized> Default for SyncSlot<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: ?Sized> SyncSlot<T> {
+    /// Create a new slot.
+    pub fn new() -> Self {
+        Self {
+            item: None,
+            name: None,
+        }
+    }
+
+    /// Create a new slot with a name, see [Slot::named](crate::Slot::named).
+    pub fn named(name: &'static str) -> Self {
+        Self {
+            item: None,
+            name: Some(name),
+        }
+    }
+
+    /// Name given to this slot by [SyncSlot::named], if any.
+    pub fn name(&self) -> Option<&'static str> {
+        self.name
+    }
+
+    /// Insert a node into this slot, see [Slot::insert](crate::Slot::insert).
+    ///
+    /// # Panics #
+    ///
+    /// Panics if there already exists a node in this slot, use [replace](SyncSlot::replace) to
+    /// overwrite it.
+    pub fn insert(&mut self, item: SyncNode<T>) {
+        if let Err(error) = self.try_insert(item) {
+            panic!(
+                "revent: insert: {} (in slot {})",
+                error,
+                self.name.unwrap_or_else(any::type_name::<T>)
+            );
+        }
+    }
+
+    /// Insert a node into this slot, returning an [Error] instead of panicking.
+    ///
+    /// Fails with [Error::OccupiedSlot] if there already exists a node in this slot, in which
+    /// case `item` is dropped and the slot is left untouched.
+    pub fn try_insert(&mut self, item: SyncNode<T>) -> Result<(), Error> {
+        if self.item.is_some() {
+            return Err(Error::OccupiedSlot);
+        }
+        self.item = Some(item);
+        Ok(())
+    }
+
+    /// Insert a node into this slot, returning the node it replaces, if any.
+    pub fn replace(&mut self, item: SyncNode<T>) -> Option<SyncNode<T>> {
+        self.item.replace(item)
+    }
+
+    /// Remove the currently held node from this slot.
+    ///
+    /// # Panics #
+    ///
+    /// Panics if there exists no node in this slot.
+    pub fn remove(&mut self) -> SyncNode<T> {
+        self.item.take().unwrap()
+    }
+
+    /// Apply a function to the node in this slot.
+    ///
+    /// # Panics #
+    ///
+    /// Panics if there exists no node in this slot, or if the node can not be borrowed.
+    pub fn emit<R>(&self, handler: impl FnOnce(&mut T) -> R) -> R {
+        match self.try_emit(handler) {
+            Ok(value) => value,
+            Err(error) => panic!(
+                "revent: emit: {} (in slot {}){}",
+                error,
+                self.name.unwrap_or_else(any::type_name::<T>),
+                stack_dump(&error)
+            ),
+        }
+    }
+
+    /// Apply a function to the node in this slot, returning an [Error] instead of panicking.
+    pub fn try_emit<R>(&self, handler: impl FnOnce(&mut T) -> R) -> Result<R, Error> {
+        let address: *const Self = self;
+        let _indent = Trace::indent(
+            TraceKind::Slot,
+            address as *const () as usize,
+            self.name,
+            any::type_name::<T>(),
+        );
+
+        self.item
+            .as_ref()
+            .map(|x| x.try_emit(handler))
+            .unwrap_or(Err(Error::EmptySlot))
+    }
+}
+
+impl<T: ?Sized> fmt::Debug for SyncSlot<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SyncSlot")
+            .field("name", &self.name)
+            .field("type_name", &any::type_name::<T>())
+            .field("node", &self.item)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use std::{
+        sync::{mpsc, Arc, Barrier},
+        thread,
+    };
+
+    #[test]
+    fn owner_is_released_after_suspension() {
+        let node = SyncNode::named("node", 0);
+        let (sender, receiver) = mpsc::channel::<SyncNode<i32>>();
+        let (results, result) = mpsc::channel();
+
+        let worker = thread::spawn(move || {
+            for node in receiver {
+                results.send(node.try_emit(|x| *x)).unwrap();
+            }
+        });
+
+        node.emit(|x| {
+            x.suspend(|| {
+                node.emit(|_| {});
+                // The outer emission is suspended, but this thread still owns the node.
+                sender.send(node.clone()).unwrap();
+                assert_eq!(
+                    result.recv().unwrap(),
+                    Err(Error::HeldByOtherThread(vec!["node"]))
+                );
+            });
+            *x = 1;
+        });
+
+        sender.send(node.clone()).unwrap();
+        assert_eq!(result.recv().unwrap(), Ok(1));
+
+        drop(sender);
+        worker.join().unwrap();
+    }
+
+    #[cfg(feature = "nightly")]
+    #[test]
+    fn implicit_coercion() {
+        let node: SyncNode<dyn std::fmt::Debug + Send> = SyncNode::new(123);
+        node.emit(|x| assert_eq!(format!("{:?}", x), "123"));
+    }
+
+    #[test]
+    fn nests_with_node() {
+        let sync = SyncNode::new(0u8);
+        let node = Node::new(0u16);
+
+        sync.emit(|x| {
+            node.emit(|y| {
+                assert_eq!(
+                    x.try_suspend(|| {}),
+                    Err(Error::NotTopOfStack {
+                        expected: "u16",
+                        actual: "u8"
+                    })
+                );
+                y.suspend(|| {
+                    assert!(sync.try_emit(|_| {}).is_err());
+                });
+            });
+            x.suspend(|| sync.emit(|x| *x += 1));
+        });
+
+        assert_eq!(sync.emit(|x| *x), 1);
+    }
+
+    #[test]
+    fn slot_insert_is_strict() {
+        let mut slot = SyncSlot::named("hud");
+        slot.insert(SyncNode::new(1));
+        assert_eq!(slot.try_insert(SyncNode::new(2)), Err(Error::OccupiedSlot));
+        assert_eq!(slot.emit(|x| *x), 1);
+
+        let old = slot.replace(SyncNode::new(3)).unwrap();
+        assert_eq!(old.emit(|x| *x), 1);
+        assert_eq!(slot.emit(|x| *x), 3);
+    }
+
+    #[test]
+    fn channel_changes_apply_to_later_emissions() {
+        let channel = SyncChannel::new();
+        channel.insert(0, SyncNode::new(1));
+
+        let mut visited = vec![];
+        channel.emit(|x| {
+            visited.push(*x);
+            channel.insert(0, SyncNode::new(2));
+        });
+        assert_eq!(visited, [1]);
+
+        visited.clear();
+        channel.emit(|x| visited.push(*x));
+        assert_eq!(visited, [1, 2]);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn par_emit_reports_conflicts() {
+        let channel = SyncChannel::new();
+        let node = SyncNode::named("node", 0);
+        channel.insert(0, SyncNode::new(0));
+        channel.insert(1, node.clone());
+
+        let result = node.emit(|_| channel.try_par_emit(|x| *x += 1));
+        assert!(matches!(
+            result,
+            Err(Error::HeldByOtherThread(_)) | Err(Error::AlreadyBorrowed(_))
+        ));
+
+        channel.insert(2, node.clone());
+        channel.par_emit(|x| *x += 1);
+        assert_eq!(node.emit(|x| *x), 2);
+    }
+
+    #[test]
+    fn contended_emissions_do_not_deadlock() {
+        let first = SyncNode::new(0);
+        let second = SyncNode::new(0);
+        let barrier = Arc::new(Barrier::new(2));
+
+        let spawn = |outer: SyncNode<i32>, inner: SyncNode<i32>| {
+            let barrier = barrier.clone();
+            thread::spawn(move || {
+                outer.emit(|_| {
+                    barrier.wait();
+                    let result = inner.try_emit(|_| {});
+                    barrier.wait();
+                    result
+                })
+            })
+        };
+
+        let a = spawn(first.clone(), second.clone());
+        let b = spawn(second, first);
+
+        assert!(matches!(
+            a.join().unwrap(),
+            Err(Error::HeldByOtherThread(_))
+        ));
+        assert!(matches!(
+            b.join().unwrap(),
+            Err(Error::HeldByOtherThread(_))
+        ));
+    }
+}
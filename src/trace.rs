@@ -4,12 +4,138 @@ use {
     std::{
         cell::{Cell, RefCell},
         rc::Rc,
+        time::Instant,
     },
 };
 
 #[cfg(feature = "trace")]
 thread_local! {
-    static BUMP: Cell<usize> = Cell::new(0);
+    static BUMP: Cell<usize> = const { Cell::new(0) };
+    static SINK: RefCell<Option<Rc<dyn TraceSink>>> = const { RefCell::new(None) };
+}
+
+/// What a [TraceEvent] describes.
+#[cfg_attr(not(feature = "trace"), allow(dead_code))]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum TraceKind {
+    /// Emitting a [Node](crate::Node).
+    Node,
+    /// Emitting a [Slot](crate::Slot).
+    Slot,
+    /// Emitting a [Channel](crate::Channel).
+    Channel,
+    /// A node [suspend](crate::Suspend::suspend)ing itself, always an [TracePhase::Enter].
+    Suspend,
+    /// A suspended node being borrowed again, always an [TracePhase::Exit].
+    Resume,
+}
+
+/// Whether a [TraceEvent] starts or ends an operation.
+#[cfg(feature = "trace")]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum TracePhase {
+    /// The operation starts.
+    Enter,
+    /// The operation ends, also reported during unwinding.
+    Exit,
+}
+
+/// Event delivered to the [TraceSink] of the current thread.
+///
+/// Every [TracePhase::Enter] is followed by a matching [TracePhase::Exit] with the same `id`
+/// and `depth`, so the events form a tree of who called whom.
+#[cfg(feature = "trace")]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TraceEvent {
+    /// Start or end of the operation.
+    pub phase: TracePhase,
+    /// Kind of the operation.
+    pub kind: TraceKind,
+    /// Address identifying the node or container, shared by all clones of a node.
+    pub id: usize,
+    /// Name given to the node or container, if any.
+    pub name: Option<&'static str>,
+    /// Name of the type held by the node or container.
+    pub type_name: &'static str,
+    /// Nesting level of the operation.
+    pub depth: usize,
+    /// Time at which the event happened.
+    pub timestamp: Instant,
+}
+
+/// Receiver of [TraceEvent]s, installed using [set_trace_sink].
+///
+/// Implemented for closures taking a `&TraceEvent`.
+///
+/// ```
+/// use revent::{Node, TraceEvent, TraceKind, TracePhase};
+/// use std::{cell::RefCell, rc::Rc};
+///
+/// let events = Rc::new(RefCell::new(Vec::new()));
+/// let capture = events.clone();
+/// revent::set_trace_sink(Some(Rc::new(move |event: &TraceEvent| {
+///     capture.borrow_mut().push((event.phase, event.kind));
+/// })));
+///
+/// Node::new(()).emit(|_| {});
+///
+/// assert_eq!(
+///     *events.borrow(),
+///     [
+///         (TracePhase::Enter, TraceKind::Node),
+///         (TracePhase::Exit, TraceKind::Node),
+///     ]
+/// );
+/// ```
+#[cfg(feature = "trace")]
+pub trait TraceSink {
+    /// Handle a single event.
+    fn event(&self, event: &TraceEvent);
+}
+
+#[cfg(feature = "trace")]
+impl<F: Fn(&TraceEvent)> TraceSink for F {
+    fn event(&self, event: &TraceEvent) {
+        (self)(event)
+    }
+}
+
+/// Install the [TraceSink] receiving all events of the current thread, returning the previous
+/// sink.
+///
+/// Passing `None`, the default, stops delivering events.
+#[cfg(feature = "trace")]
+pub fn set_trace_sink(sink: Option<Rc<dyn TraceSink>>) -> Option<Rc<dyn TraceSink>> {
+    SINK.with(|x| x.replace(sink))
+}
+
+#[cfg(feature = "trace")]
+fn depth() -> usize {
+    let count = STACK.with(|x| unsafe { &*x.get() }.len());
+    let bump = BUMP.with(|x| x.get());
+    count + bump
+}
+
+#[cfg(feature = "trace")]
+fn record(
+    phase: TracePhase,
+    kind: TraceKind,
+    id: usize,
+    name: Option<&'static str>,
+    type_name: &'static str,
+) {
+    // The sink is cloned out so it may replace itself while handling the event.
+    if let Some(sink) = SINK.with(|x| x.borrow().clone()) {
+        sink.event(&TraceEvent {
+            phase,
+            kind,
+            id,
+            name,
+            type_name,
+            depth: depth(),
+            timestamp: Instant::now(),
+        });
+    }
 }
 
 #[cfg(feature = "trace")]
@@ -36,24 +162,110 @@ impl Trace {
     }
 
     pub fn log(&self) {
-        let count = STACK.with(|x| unsafe { &*x.get() }.len());
-        let bump = BUMP.with(|x| x.get());
-        (*self.logger.borrow())(count + bump);
+        (*self.logger.borrow())(depth());
     }
 
-    pub fn indent() {
+    pub fn span(
+        kind: TraceKind,
+        id: usize,
+        name: Option<&'static str>,
+        type_name: &'static str,
+    ) -> Span {
+        record(TracePhase::Enter, kind, id, name, type_name);
+        Span {
+            kind,
+            id,
+            name,
+            type_name,
+            _tracing: tracing_span(kind, id, name, type_name),
+        }
+    }
+
+    pub fn indent(
+        kind: TraceKind,
+        id: usize,
+        name: Option<&'static str>,
+        type_name: &'static str,
+    ) -> Indent {
+        let span = Self::span(kind, id, name, type_name);
         BUMP.with(|x| {
             x.set(x.get() + 1);
         });
+        Indent { _span: span }
+    }
+
+    pub fn suspend(id: usize, name: Option<&'static str>, type_name: &'static str) -> Suspension {
+        record(TracePhase::Enter, TraceKind::Suspend, id, name, type_name);
+        Suspension {
+            id,
+            name,
+            type_name,
+            _tracing: tracing_span(TraceKind::Suspend, id, name, type_name),
+        }
     }
+}
+
+/// Records the exit of an operation when dropped, also during unwinding.
+#[cfg(feature = "trace")]
+pub struct Span {
+    kind: TraceKind,
+    id: usize,
+    name: Option<&'static str>,
+    type_name: &'static str,
+    _tracing: Tracing,
+}
 
-    pub fn dedent() {
+#[cfg(feature = "trace")]
+impl Drop for Span {
+    fn drop(&mut self) {
+        record(
+            TracePhase::Exit,
+            self.kind,
+            self.id,
+            self.name,
+            self.type_name,
+        );
+    }
+}
+
+/// Dedents the trace when dropped, also during unwinding.
+#[cfg(feature = "trace")]
+pub struct Indent {
+    // Dropped after dedenting so the exit has the same depth as the enter.
+    _span: Span,
+}
+
+#[cfg(feature = "trace")]
+impl Drop for Indent {
+    fn drop(&mut self) {
         BUMP.with(|x| {
             x.set(x.get() - 1);
         });
     }
 }
 
+/// Records the resumption of a suspended node when dropped.
+#[cfg(feature = "trace")]
+pub struct Suspension {
+    id: usize,
+    name: Option<&'static str>,
+    type_name: &'static str,
+    _tracing: Tracing,
+}
+
+#[cfg(feature = "trace")]
+impl Drop for Suspension {
+    fn drop(&mut self) {
+        record(
+            TracePhase::Exit,
+            TraceKind::Resume,
+            self.id,
+            self.name,
+            self.type_name,
+        );
+    }
+}
+
 #[cfg(not(feature = "trace"))]
 #[derive(Clone)]
 pub struct Trace;
@@ -77,8 +289,303 @@ impl Trace {
     pub fn log(&self) {}
 
     #[inline]
-    pub fn indent() {}
+    pub fn span(
+        kind: TraceKind,
+        id: usize,
+        name: Option<&'static str>,
+        type_name: &'static str,
+    ) -> Span {
+        Span {
+            _tracing: tracing_span(kind, id, name, type_name),
+        }
+    }
 
     #[inline]
-    pub fn dedent() {}
+    pub fn indent(
+        kind: TraceKind,
+        id: usize,
+        name: Option<&'static str>,
+        type_name: &'static str,
+    ) -> Indent {
+        Indent {
+            _span: Self::span(kind, id, name, type_name),
+        }
+    }
+
+    #[inline]
+    pub fn suspend(id: usize, name: Option<&'static str>, type_name: &'static str) -> Suspension {
+        Suspension {
+            _tracing: tracing_span(TraceKind::Suspend, id, name, type_name),
+        }
+    }
+}
+
+#[cfg(not(feature = "trace"))]
+pub struct Span {
+    _tracing: Tracing,
+}
+
+#[cfg(not(feature = "trace"))]
+pub struct Indent {
+    _span: Span,
+}
+
+#[cfg(not(feature = "trace"))]
+pub struct Suspension {
+    _tracing: Tracing,
+}
+
+// Span of the `tracing` crate, open while the guard holding it is alive.
+#[cfg(feature = "tracing")]
+type Tracing = ::tracing::span::EnteredSpan;
+#[cfg(not(feature = "tracing"))]
+type Tracing = ();
+
+#[cfg(feature = "tracing")]
+fn tracing_span(
+    kind: TraceKind,
+    id: usize,
+    name: Option<&'static str>,
+    type_name: &'static str,
+) -> Tracing {
+    let span = match kind {
+        TraceKind::Node => ::tracing::trace_span!("emit", id, name, type_name),
+        TraceKind::Slot => ::tracing::trace_span!("slot", id, name, type_name),
+        TraceKind::Channel => ::tracing::trace_span!("channel", id, name, type_name),
+        TraceKind::Suspend | TraceKind::Resume => {
+            ::tracing::trace_span!("suspend", id, name, type_name)
+        }
+    };
+    span.entered()
+}
+
+#[cfg(not(feature = "tracing"))]
+#[inline(always)]
+fn tracing_span(_: TraceKind, _: usize, _: Option<&'static str>, _: &'static str) -> Tracing {}
+
+#[cfg(all(test, feature = "trace"))]
+mod tests {
+    use crate::{TracePhase::*, *};
+    use std::{
+        cell::RefCell,
+        panic::{self, AssertUnwindSafe},
+        rc::Rc,
+    };
+
+    fn capture() -> Rc<RefCell<Vec<TraceEvent>>> {
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let capture = events.clone();
+        set_trace_sink(Some(Rc::new(move |event: &TraceEvent| {
+            capture.borrow_mut().push(*event);
+        })));
+        events
+    }
+
+    #[test]
+    fn events_form_call_tree() {
+        let events = capture();
+
+        let channel = Channel::new();
+        let outer = Node::new(0u8);
+        let inner = Node::named("inner", 0u16);
+        channel.insert(0, outer.clone());
+
+        channel.emit(|x| {
+            x.suspend(|| {
+                inner.emit(|_| {});
+            });
+        });
+        set_trace_sink(None);
+
+        let events = events.borrow();
+        let summary = events
+            .iter()
+            .map(|x| (x.phase, x.kind, x.depth))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            [
+                (Enter, TraceKind::Channel, 0),
+                (Enter, TraceKind::Node, 1),
+                (Enter, TraceKind::Suspend, 2),
+                (Enter, TraceKind::Node, 2),
+                (Exit, TraceKind::Node, 2),
+                (Exit, TraceKind::Resume, 2),
+                (Exit, TraceKind::Node, 1),
+                (Exit, TraceKind::Channel, 0),
+            ]
+        );
+
+        assert_eq!(events[1].id, events[2].id);
+        assert_eq!(events[1].id, events[6].id);
+        assert_ne!(events[1].id, events[3].id);
+        assert_eq!(events[1].type_name, "u8");
+        assert_eq!(events[3].type_name, "u16");
+        assert_eq!(events[1].name, None);
+        assert_eq!(events[3].name, Some("inner"));
+        assert!(events.windows(2).all(|x| x[0].timestamp <= x[1].timestamp));
+    }
+
+    #[test]
+    fn emit_iter_traces_each_step() {
+        let events = capture();
+
+        let channel = Channel::new();
+        channel.insert(0, Node::new(0u8));
+        channel.insert(1, Node::new(1u8));
+        let other = Node::new(());
+
+        let mut iter = channel.emit_iter(|x| *x);
+        assert_eq!(iter.next(), Some(0));
+        other.emit(|_| {});
+        assert_eq!(iter.next(), Some(1));
+        drop(iter);
+        set_trace_sink(None);
+
+        let summary = events
+            .borrow()
+            .iter()
+            .map(|x| (x.phase, x.kind, x.depth))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            [
+                (Enter, TraceKind::Channel, 0),
+                (Enter, TraceKind::Node, 1),
+                (Exit, TraceKind::Node, 1),
+                (Exit, TraceKind::Channel, 0),
+                (Enter, TraceKind::Node, 0),
+                (Exit, TraceKind::Node, 0),
+                (Enter, TraceKind::Channel, 0),
+                (Enter, TraceKind::Node, 1),
+                (Exit, TraceKind::Node, 1),
+                (Exit, TraceKind::Channel, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn exit_is_recorded_on_panic() {
+        let events = capture();
+
+        let mut slot = Slot::new();
+        slot.insert(Node::new(()));
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            slot.emit(|_| panic!());
+        }));
+        set_trace_sink(None);
+        assert!(result.is_err());
+
+        let kinds = events
+            .borrow()
+            .iter()
+            .map(|x| (x.phase, x.kind))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            [
+                (Enter, TraceKind::Slot),
+                (Enter, TraceKind::Node),
+                (Exit, TraceKind::Node),
+                (Exit, TraceKind::Slot),
+            ]
+        );
+    }
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tracing_tests {
+    use crate::*;
+    use std::{
+        fmt::Debug,
+        sync::{Arc, Mutex},
+    };
+    use tracing::{
+        field::{Field, Visit},
+        span::{Attributes, Id, Record},
+        Event, Metadata, Subscriber,
+    };
+
+    // Records entered and exited spans along with their fields.
+    #[derive(Clone, Default)]
+    struct Recorder {
+        spans: Arc<Mutex<Vec<String>>>,
+        log: Arc<Mutex<Vec<String>>>,
+    }
+
+    struct Fields(String);
+
+    impl Visit for Fields {
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            self.0.push_str(&format!(" {}={:?}", field.name(), value));
+        }
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let mut fields = Fields(span.metadata().name().to_string());
+            span.record(&mut fields);
+
+            let mut spans = self.spans.lock().unwrap();
+            spans.push(fields.0);
+            Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, _: &Id, _: &Record<'_>) {}
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+        fn event(&self, _: &Event<'_>) {}
+
+        fn enter(&self, span: &Id) {
+            let description = &self.spans.lock().unwrap()[span.into_u64() as usize - 1];
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("enter {}", description));
+        }
+
+        fn exit(&self, span: &Id) {
+            let description = &self.spans.lock().unwrap()[span.into_u64() as usize - 1];
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("exit {}", description));
+        }
+    }
+
+    #[test]
+    fn spans_follow_emissions() {
+        let recorder = Recorder::default();
+
+        tracing::subscriber::with_default(recorder.clone(), || {
+            let mut slot = Slot::new();
+            slot.insert(Node::new(0u8));
+            slot.emit(|x| {
+                x.suspend(|| {});
+            });
+        });
+
+        let log = recorder.log.lock().unwrap();
+        let names = log
+            .iter()
+            .map(|x| x.split(" id=").next().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "enter slot",
+                "enter emit",
+                "enter suspend",
+                "exit suspend",
+                "exit emit",
+                "exit slot",
+            ]
+        );
+        assert!(log.iter().all(|x| x.ends_with(r#" type_name="u8""#)));
+    }
 }